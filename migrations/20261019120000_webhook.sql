@@ -0,0 +1,4 @@
+-- Optional webhook delivery with a custom identity per channel
+ALTER TABLE FRONT ADD Webhook_URL TEXT;
+ALTER TABLE FRONT ADD Webhook_Username TEXT;
+ALTER TABLE FRONT ADD Webhook_Avatar TEXT;
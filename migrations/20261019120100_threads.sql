@@ -0,0 +1,6 @@
+-- Forum posts created for each series in forum channels
+CREATE TABLE THREADS (
+    Channel_ID INTEGER NOT NULL,
+    SeriesID TEXT NOT NULL,
+    Thread_ID INTEGER NOT NULL
+)
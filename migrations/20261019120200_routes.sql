@@ -0,0 +1,25 @@
+-- Give every instance a stable id to reference it from other tables
+CREATE TABLE FRONT_NEW (
+    ID INTEGER PRIMARY KEY AUTOINCREMENT,
+    Active_Channel INTEGER NOT NULL,
+    Channel_ID INTEGER NOT NULL,
+    Domain TEXT NOT NULL,
+    Token TEXT NOT NULL,
+    UserID TEXT NOT NULL,
+    Webhook_URL TEXT,
+    Webhook_Username TEXT,
+    Webhook_Avatar TEXT
+);
+INSERT INTO FRONT_NEW (Active_Channel, Channel_ID, Domain, Token, UserID, Webhook_URL, Webhook_Username, Webhook_Avatar)
+    SELECT Active_Channel, Channel_ID, Domain, Token, UserID, Webhook_URL, Webhook_Username, Webhook_Avatar FROM FRONT;
+DROP TABLE FRONT;
+ALTER TABLE FRONT_NEW RENAME TO FRONT;
+
+-- Announcements matching a rule are sent to its channel instead of the instance's channel
+CREATE TABLE ROUTES (
+    ID INTEGER PRIMARY KEY AUTOINCREMENT,
+    Instance_ID INTEGER NOT NULL,
+    Kind TEXT NOT NULL,
+    Value TEXT NOT NULL,
+    Channel_ID INTEGER NOT NULL
+);
@@ -0,0 +1,7 @@
+-- 0 = all libraries, 1 = only the listed libraries, 2 = all but the listed libraries
+ALTER TABLE FRONT ADD Library_Mode INTEGER NOT NULL DEFAULT 0;
+
+CREATE TABLE LIBRARIES (
+    Instance_ID INTEGER NOT NULL,
+    LibraryID TEXT NOT NULL
+);
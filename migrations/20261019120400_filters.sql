@@ -0,0 +1,7 @@
+-- Announcements matching a filter are skipped and only marked as seen
+CREATE TABLE FILTERS (
+    ID INTEGER PRIMARY KEY AUTOINCREMENT,
+    Instance_ID INTEGER NOT NULL,
+    Kind TEXT NOT NULL,
+    Value TEXT NOT NULL
+);
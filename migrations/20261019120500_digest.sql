@@ -0,0 +1,12 @@
+-- Digest mode: announcements are queued and posted as one summary on a schedule
+ALTER TABLE FRONT ADD Digest_Schedule TEXT;
+ALTER TABLE FRONT ADD Digest_Timezone TEXT;
+ALTER TABLE FRONT ADD Digest_Last INTEGER;
+
+-- Rendered announcements (as json) waiting to be posted
+CREATE TABLE PENDING (
+    ID INTEGER PRIMARY KEY AUTOINCREMENT,
+    Instance_ID INTEGER NOT NULL,
+    Announcement TEXT NOT NULL,
+    Created INTEGER NOT NULL
+);
@@ -0,0 +1,5 @@
+-- Announcements are held back between Quiet_Start and Quiet_End ("HH:MM")
+ALTER TABLE FRONT ADD Quiet_Start TEXT;
+ALTER TABLE FRONT ADD Quiet_End TEXT;
+ALTER TABLE FRONT ADD Quiet_Timezone TEXT;
+ALTER TABLE FRONT ADD Quiet_Collapse INTEGER NOT NULL DEFAULT 0;
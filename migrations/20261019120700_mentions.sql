@@ -0,0 +1,8 @@
+-- Roles pinged for announcements matching Kind and Value
+CREATE TABLE MENTIONS (
+    ID INTEGER PRIMARY KEY AUTOINCREMENT,
+    Instance_ID INTEGER NOT NULL,
+    Kind TEXT NOT NULL,
+    Value TEXT NOT NULL,
+    Role_ID INTEGER NOT NULL
+);
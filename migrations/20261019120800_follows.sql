@@ -0,0 +1,20 @@
+-- Guild of the announcement channel, so members can find the instances of their server
+ALTER TABLE FRONT ADD Guild_ID INTEGER;
+
+-- Series members follow to be notified through direct messages
+CREATE TABLE FOLLOWS (
+    ID INTEGER PRIMARY KEY AUTOINCREMENT,
+    User_ID INTEGER NOT NULL,
+    Instance_ID INTEGER NOT NULL,
+    Series_ID TEXT NOT NULL,
+    Series_Name TEXT NOT NULL,
+    UNIQUE (User_ID, Instance_ID, Series_ID)
+);
+
+-- Direct message opt-out and rate limit per member
+CREATE TABLE DM_SETTINGS (
+    User_ID INTEGER PRIMARY KEY,
+    Enabled INTEGER NOT NULL DEFAULT 1,
+    Window_Start INTEGER NOT NULL DEFAULT 0,
+    Sent INTEGER NOT NULL DEFAULT 0
+);
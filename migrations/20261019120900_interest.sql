@@ -0,0 +1,18 @@
+-- Messages announcements have been posted as
+CREATE TABLE ANNOUNCEMENTS (
+    ID INTEGER PRIMARY KEY AUTOINCREMENT,
+    Instance_ID INTEGER NOT NULL,
+    Item_ID TEXT NOT NULL,
+    Title TEXT NOT NULL,
+    Channel_ID INTEGER NOT NULL,
+    Message_ID INTEGER NOT NULL,
+    Created INTEGER NOT NULL
+);
+
+-- Members who want to watch an announced item
+CREATE TABLE INTEREST (
+    Instance_ID INTEGER NOT NULL,
+    Item_ID TEXT NOT NULL,
+    User_ID INTEGER NOT NULL,
+    PRIMARY KEY (Instance_ID, Item_ID, User_ID)
+);
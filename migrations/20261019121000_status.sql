@@ -0,0 +1,6 @@
+-- Name of the jellyfin user, shown by /status
+ALTER TABLE FRONT ADD User_Name TEXT;
+-- Outcome of the polls
+ALTER TABLE FRONT ADD Last_Poll INTEGER;
+ALTER TABLE FRONT ADD Last_Error TEXT;
+ALTER TABLE FRONT ADD Last_Error_At INTEGER;
@@ -0,0 +1,5 @@
+-- Paused instances (Active_Channel = 0) resume on their own after Pause_Until
+ALTER TABLE FRONT ADD Pause_Until INTEGER;
+ALTER TABLE FRONT ADD Pause_Reason TEXT;
+-- 0 = items arriving during the pause are marked as seen, 1 = they're queued
+ALTER TABLE FRONT ADD Pause_Queue INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,5 @@
+-- LIBRARY columns of reset instances, kept for a while so a new /init resumes silently
+CREATE TABLE ARCHIVE (
+    User_ID TEXT PRIMARY KEY,
+    Archived INTEGER NOT NULL
+);
@@ -0,0 +1,2 @@
+-- 0 = announcing, 1 = too many new items, waiting for an admin, 2 = confirmed to announce them
+ALTER TABLE FRONT ADD Valve_State INTEGER NOT NULL DEFAULT 0;
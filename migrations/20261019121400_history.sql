@@ -0,0 +1,21 @@
+-- Announcements keep their type, delivery status and content, so they can be posted again.
+-- Failed deliveries have no message, which needs the table to be rebuilt.
+CREATE TABLE ANNOUNCEMENTS_NEW (
+    ID INTEGER PRIMARY KEY AUTOINCREMENT,
+    Instance_ID INTEGER NOT NULL,
+    Item_ID TEXT NOT NULL,
+    Title TEXT NOT NULL,
+    Type TEXT NOT NULL DEFAULT '',
+    Channel_ID INTEGER,
+    Message_ID INTEGER,
+    Created INTEGER NOT NULL,
+    Status TEXT NOT NULL DEFAULT 'posted',
+    Announcement TEXT
+);
+
+INSERT INTO ANNOUNCEMENTS_NEW (ID, Instance_ID, Item_ID, Title, Channel_ID, Message_ID, Created)
+SELECT ID, Instance_ID, Item_ID, Title, Channel_ID, Message_ID, Created FROM ANNOUNCEMENTS;
+
+DROP TABLE ANNOUNCEMENTS;
+
+ALTER TABLE ANNOUNCEMENTS_NEW RENAME TO ANNOUNCEMENTS;
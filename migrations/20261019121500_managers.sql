@@ -0,0 +1,5 @@
+-- Settings of a discord server, like the role allowed to manage announcements
+CREATE TABLE GUILDS (
+    Guild_ID INTEGER PRIMARY KEY,
+    Manager_Role INTEGER
+);
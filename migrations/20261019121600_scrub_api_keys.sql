@@ -0,0 +1,13 @@
+-- Season and series images used to carry the API key, which ended up in every stored
+-- announcement. The parameter is cut out up to the next one, which is always "Quality".
+UPDATE ANNOUNCEMENTS
+SET Announcement = substr(Announcement, 1, instr(Announcement, 'api_key=') - 1)
+    || substr(Announcement, instr(Announcement, 'api_key=') + instr(substr(Announcement, instr(Announcement, 'api_key=')), '&'))
+WHERE instr(Announcement, 'api_key=') > 0
+    AND instr(substr(Announcement, instr(Announcement, 'api_key=')), '&') > 0;
+
+UPDATE PENDING
+SET Announcement = substr(Announcement, 1, instr(Announcement, 'api_key=') - 1)
+    || substr(Announcement, instr(Announcement, 'api_key=') + instr(substr(Announcement, instr(Announcement, 'api_key=')), '&'))
+WHERE instr(Announcement, 'api_key=') > 0
+    AND instr(substr(Announcement, instr(Announcement, 'api_key=')), '&') > 0;
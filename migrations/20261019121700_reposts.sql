@@ -0,0 +1,2 @@
+-- Announcements posted again through /history, which aren't new additions
+ALTER TABLE ANNOUNCEMENTS ADD COLUMN Repost INTEGER NOT NULL DEFAULT 0;
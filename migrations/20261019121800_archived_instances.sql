@@ -0,0 +1,6 @@
+-- Instances reset with their seen items archived, whose history goes to the next /init of the same user and channel
+CREATE TABLE ARCHIVED_INSTANCES (
+    Instance_ID INTEGER PRIMARY KEY,
+    User_ID TEXT NOT NULL,
+    Channel_ID INTEGER NOT NULL
+);
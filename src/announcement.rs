@@ -0,0 +1,886 @@
+use serde_derive::{Deserialize, Serialize};
+use serenity::all::{
+  ButtonStyle, Channel, ChannelId, ChannelType, Context, CreateActionRow, CreateAllowedMentions,
+  CreateButton, CreateEmbed, CreateForumPost, CreateMessage, ExecuteWebhook, ForumTagId,
+  GuildChannel, Message, MessageId, ReactionType, RoleId, Webhook,
+};
+
+use crate::database::{
+  get_interest, get_mentions, get_routes, get_thread, record_announcement, remove_thread,
+  set_thread,
+};
+use crate::{Filter, Instance, Item, Mention, Route, Type};
+
+/// Everything needed to post a single embed pair for one or more library items.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Announcement {
+  /// The item the announcement is about. For episode groups this is the series.
+  pub item: Item,
+  /// The type of the announced items, e.g. `Episode` for a group of episodes.
+  pub kind: Type,
+  /// Every id that will be marked as seen once the announcement went out.
+  pub ids: Vec<String>,
+  pub title: String,
+  pub image: String,
+  pub description: Option<String>,
+  pub fields: Vec<(String, String, bool)>,
+  /// The series of an episode or season. Jellyfin rarely fills tags, genres or ratings on
+  /// those, so rules look at the series as well.
+  #[serde(default)]
+  pub series: Option<Item>,
+}
+
+impl Announcement {
+  pub fn embeds(&self) -> Vec<CreateEmbed> {
+    let mut header = CreateEmbed::new().title(&self.title).image(&self.image);
+    if let Some(description) = &self.description {
+      header = header.description(description);
+    }
+
+    if self.fields.is_empty() {
+      return vec![header];
+    }
+    let mut embed = CreateEmbed::default();
+    for (name, value, inline) in &self.fields {
+      embed = embed.field(name.clone(), value.clone(), *inline);
+    }
+
+    vec![header, embed]
+  }
+
+  /// Returns the first filter that prevents this announcement from being posted.
+  ///
+  /// `items` is used to look up the audio streams of every announced item.
+  pub fn filtered_by<'a>(&self, filters: &'a [Filter], items: &[Item]) -> Option<&'a Filter> {
+    filters.iter().find(|filter| match filter.kind.as_str() {
+      // Rating systems differ by country, so one filter can list several, e.g. "R,NC-17,TV-MA".
+      "rating" => self.subjects().any(|item| {
+        item.OfficialRating.as_ref().is_some_and(|rating| {
+          filter
+            .value
+            .split(',')
+            .any(|value| rating.eq_ignore_ascii_case(value.trim()))
+        })
+      }),
+      "tag" => self.subjects().any(|item| {
+        item
+          .Tags
+          .iter()
+          .flatten()
+          .any(|tag| tag.eq_ignore_ascii_case(&filter.value))
+      }),
+      "genre" => self.subjects().any(|item| {
+        item
+          .Genres
+          .iter()
+          .flatten()
+          .any(|genre| genre.eq_ignore_ascii_case(&filter.value))
+      }),
+      // Unrated items are kept, they would never be announced otherwise.
+      "score" => match (filter.value.parse::<f64>(), self.rating()) {
+        (Ok(min), Some(rating)) => rating < min,
+        _ => false,
+      },
+      // Series and seasons have no streams of their own and are never skipped for their audio.
+      "audio" => {
+        let mut audio = items
+          .iter()
+          .filter(|x| self.ids.contains(&x.Id))
+          .flat_map(|x| x.MediaStreams.iter().flatten())
+          .filter(|stream| stream.Type == "Audio")
+          .peekable();
+        audio.peek().is_some()
+          && !audio.any(|stream| {
+            stream
+              .Language
+              .as_ref()
+              .is_some_and(|language| language.eq_ignore_ascii_case(&filter.value))
+          })
+      },
+      _ => false,
+    })
+  }
+
+  /// The announced item and, for episodes and seasons, its series.
+  pub fn subjects(&self) -> impl Iterator<Item = &Item> {
+    std::iter::once(&self.item).chain(self.series.as_ref())
+  }
+
+  /// The community rating of the series, or of the item itself.
+  fn rating(&self) -> Option<f64> {
+    self
+      .series
+      .as_ref()
+      .and_then(|series| series.CommunityRating)
+      .or(self.item.CommunityRating)
+  }
+}
+
+/// Turns one group of new items into an announcement.
+///
+/// `itemlist` is a group as built by the main loop: either a single movie, series, season or
+/// episode, or several episodes of the same season.
+pub fn render(
+  server: &Instance,
+  itemlist: &mut [Item],
+  all_items: &[Item],
+  pre_season_items: &[Item],
+  pre_episode_items: &[Item],
+) -> Option<Announcement> {
+  let announcement = if itemlist.len() == 1 {
+    let item = itemlist[0].clone();
+    if let Some(streams) = &item.MediaStreams
+      && streams.is_empty()
+    {
+      return None;
+    }
+
+    if item.Type == Type::Episode || item.Type == Type::Special || item.Type == Type::Movie {
+      render_single(server, item)
+    } else if item.Type == Type::Season || item.Type == Type::Series {
+      render_season(server, item, pre_season_items, pre_episode_items)
+    } else {
+      return None;
+    }
+  } else {
+    render_episodes(server, itemlist, all_items)?
+  };
+  let series = announcement
+    .item
+    .SeriesId
+    .as_ref()
+    .and_then(|id| all_items.iter().find(|item| &item.Id == id))
+    .cloned();
+  Some(Announcement {
+    series,
+    ..announcement
+  })
+}
+
+/// Renders a single item on its own, e.g. when browsing the library.
+pub fn render_item(server: &Instance, item: Item) -> Announcement {
+  match item.Type {
+    Type::Series | Type::Season => Announcement {
+      description: None,
+      ..render_season(server, item, &[], &[])
+    },
+    _ => render_single(server, item),
+  }
+}
+
+fn render_single(server: &Instance, item: Item) -> Announcement {
+  let name = item.to_string();
+  let image = format!(
+    "{}/Items/{}/Images/Primary?Quality=100",
+    server.domain,
+    item.clone().SeasonId.unwrap_or(item.clone().Id)
+  );
+  let (resolution, a_languages, s_languages) = if let Some(streams) = item.MediaStreams.clone() {
+    let mut height: String = String::new();
+    let mut a_languages: String = String::new();
+    let mut s_languages: String = String::new();
+    let mut scan_type: char = 'p';
+    for x in streams {
+      if x.Type == "Video" {
+        height = x.Height.unwrap().to_string();
+        if x.IsInterlaced {
+          scan_type = 'i';
+        }
+      } else if x.Type == "Audio" {
+        a_languages.push_str(&(x.Language.unwrap_or("?".to_string()) + ", "))
+      } else if x.Type == "Subtitle" {
+        s_languages.push_str(&(x.Language.unwrap_or("?".to_string()) + ", "))
+      }
+    }
+    if height.is_empty() {
+      height = "?".to_string()
+    }
+    if a_languages.is_empty() {
+      a_languages = "?".to_string()
+    } else if a_languages != *"?" {
+      a_languages = a_languages.strip_suffix(", ").unwrap().to_string();
+    }
+    if s_languages != *"?" && !s_languages.is_empty() {
+      s_languages = s_languages.strip_suffix(", ").unwrap().to_string();
+    } else {
+      s_languages = String::new()
+    }
+    height.push(scan_type);
+    (height, a_languages, s_languages)
+  } else {
+    ("?".to_string(), "?".to_string(), String::new())
+  };
+  let runtime: String = if let Some(ticks) = item.RunTimeTicks {
+    format_runtime(ticks)
+  } else {
+    "?".to_string()
+  };
+
+  let mut fields = Vec::new();
+  fields.push((
+    ":star: — Rating".to_string(),
+    if let Some(rating) = item.CommunityRating {
+      format!("{:.2}", rating)
+    } else {
+      "?".to_string()
+    },
+    true,
+  ));
+  fields.push((":film_frames: — Runtime".to_string(), runtime, true));
+  fields.push((":frame_photo: — Resolution".to_string(), resolution, true));
+  fields.push((":loud_sound: — Languages".to_string(), a_languages, false));
+
+  if !s_languages.is_empty() {
+    fields.push((
+      ":notepad_spiral: — Languages".to_string(),
+      s_languages,
+      false,
+    ));
+  }
+
+  Announcement {
+    ids: vec![item.Id.clone()],
+    kind: item.Type.clone(),
+    item,
+    title: name,
+    image,
+    description: None,
+    fields,
+    series: None,
+  }
+}
+
+fn render_season(
+  server: &Instance,
+  item: Item,
+  pre_season_items: &[Item],
+  pre_episode_items: &[Item],
+) -> Announcement {
+  let mut ids: Vec<String> = vec![item.Id.clone()];
+  let seasons = if item.Type == Type::Series {
+    let mut temp = vec![];
+    for season in pre_season_items {
+      if season.SeriesId.clone().unwrap() == item.Id {
+        ids.push(season.Id.clone());
+        temp.push(season.clone());
+      }
+    }
+    temp
+  } else {
+    vec![item.clone()]
+  };
+
+  let mut desc = String::new();
+  let mut streams = StreamSummary::default();
+
+  let mut current_start = -1;
+  for season in seasons {
+    for (i, episode) in pre_episode_items.iter().enumerate() {
+      if episode.SeasonId.clone().unwrap() != season.Id {
+        continue;
+      }
+
+      ids.push(episode.Id.clone());
+      streams.add(episode);
+
+      let index_start = episode.IndexNumber.unwrap() as i32;
+      let index_end = if let Some(end) = episode.IndexNumberEnd {
+        end as i32
+      } else {
+        index_start
+      };
+      let (item_name_full, item_name_start, item_name_end) = episode_names(episode);
+
+      if pre_episode_items.len() - 1 == i {
+        if current_start == -1 {
+          desc.push_str(&item_name_full);
+        } else {
+          desc.push_str(&format!("-{}", item_name_end));
+        }
+      } else if i == 0 || current_start == -1 {
+        if pre_episode_items[i + 1].IndexNumber.unwrap() as i32 != index_end + 1 {
+          desc.push_str(&format!("{}, ", item_name_full));
+          current_start = -1;
+          continue;
+        } else {
+          desc.push_str(&item_name_start);
+        }
+      } else if pre_episode_items[i + 1].IndexNumber.unwrap() as i32 != index_end + 1 {
+        desc.push_str(&format!("-{}, ", item_name_end));
+        current_start = -1;
+        continue;
+      }
+      current_start = index_start;
+    }
+  }
+
+  let image = format!(
+    "{}/Items/{}/Images/Primary?Quality=100",
+    server.domain,
+    item.clone().SeasonId.unwrap_or(item.clone().Id)
+  );
+
+  Announcement {
+    title: item.to_string(),
+    kind: item.Type.clone(),
+    item,
+    ids,
+    image,
+    description: Some(desc),
+    fields: streams.fields(),
+    series: None,
+  }
+}
+
+fn render_episodes(
+  server: &Instance,
+  itemlist: &mut [Item],
+  all_items: &[Item],
+) -> Option<Announcement> {
+  let series_id = itemlist[0].SeriesId.clone().unwrap();
+  let mut item: Item = itemlist[0].clone();
+  for x in all_items {
+    if x.Id == series_id {
+      item = x.clone();
+      break;
+    }
+  }
+  if item.Type != Type::Series {
+    eprintln!(
+      "Failed to find a Series object that belongs to \"{}\"",
+      item.Id
+    );
+    return None;
+  }
+
+  itemlist.sort_by_key(|i| i.IndexNumber.unwrap());
+  let mut desc = String::new();
+  let mut streams = StreamSummary::default();
+
+  let mut current_start: i32 = -1;
+  for (i, episode) in itemlist.iter().enumerate() {
+    streams.add(episode);
+
+    let index_start = episode.IndexNumber.unwrap() as i32;
+    let index_end = if let Some(end) = episode.IndexNumberEnd {
+      end as i32
+    } else {
+      index_start
+    };
+    let (item_name_full, item_name_start, item_name_end) = episode_names(episode);
+
+    if itemlist.len() - 1 == i {
+      if current_start == -1 {
+        desc.push_str(&item_name_full);
+      } else {
+        desc.push_str(&format!("-{}", item_name_end));
+      }
+    } else if i == 0 || current_start == -1 {
+      if itemlist[i + 1].IndexNumber.unwrap() as i32 != index_end + 1 {
+        desc.push_str(&format!("{}, ", item_name_full));
+        current_start = -1;
+        continue;
+      } else {
+        desc.push_str(&item_name_start);
+      }
+    } else if itemlist[i + 1].IndexNumber.unwrap() as i32 != index_end + 1 {
+      desc.push_str(&format!("-{}, ", item_name_end));
+      current_start = -1;
+      continue;
+    }
+    current_start = index_start;
+  }
+
+  let image = format!(
+    "{}/Items/{}/Images/Primary?Quality=100",
+    server.domain,
+    item.clone().SeasonId.unwrap_or(item.clone().Id)
+  );
+
+  Some(Announcement {
+    title: item.to_string(),
+    kind: Type::Episode,
+    item,
+    ids: itemlist.iter().map(|x| x.Id.clone()).collect(),
+    image,
+    description: Some(desc),
+    fields: streams.fields(),
+    series: None,
+  })
+}
+
+/// Returns the full (S01E01-02), start (S01E01) and end (S01E02) name of an episode.
+fn episode_names(episode: &Item) -> (String, String, String) {
+  let season = episode.ParentIndexNumber.unwrap_or(0);
+  let index = episode.IndexNumber.unwrap_or(0);
+  match episode.IndexNumberEnd {
+    Some(indexend) => (
+      format!("S{:02}E{:02}-{:02}", season, index, indexend),
+      format!("S{:02}E{:02}", season, index),
+      format!("S{:02}E{:02}", season, indexend),
+    ),
+    None => (
+      format!("S{:02}E{:02}", season, index),
+      format!("S{:02}E{:02}", season, index),
+      format!("S{:02}E{:02}", season, index),
+    ),
+  }
+}
+
+fn format_runtime(ticks: u64) -> String {
+  let time = (ticks as f64) / 10000000.0;
+  if time > 60.0 {
+    if (time / 60.0) > 60.0 {
+      format!(
+        "{:02}:{:02}:{:02}",
+        ((time / 60.0) / 60.0).trunc(),
+        ((((time / 60.0) / 60.0) - ((time / 60.0) / 60.).trunc()) * 60.0).trunc(),
+        (((time / 60.0) - (time / 60.0).trunc()) * 60.0).trunc()
+      )
+    } else {
+      format!(
+        "00:{:02}:{:02}",
+        (time / 60.0).trunc(),
+        (((time / 60.0) - (time / 60.0).trunc()) * 60.0).trunc()
+      )
+    }
+  } else {
+    format!("00:00:{time:02}")
+  }
+}
+
+/// Collects the stream information of several episodes for the field embed.
+#[derive(Default)]
+struct StreamSummary {
+  a_languages: Vec<String>,
+  s_languages: Vec<String>,
+  v_resolutions: Vec<String>,
+  ratings: Vec<f64>,
+  total_runtime: u64,
+}
+
+impl StreamSummary {
+  fn add(&mut self, episode: &Item) {
+    if let Some(mediastreams) = &episode.MediaStreams {
+      for x in mediastreams {
+        if x.Type == "Video" {
+          let scan_type = if x.IsInterlaced { 'i' } else { 'p' };
+          let resolution = if let Some(height) = x.Height {
+            height.to_string() + &scan_type.to_string()
+          } else {
+            String::from("?") + &scan_type.to_string()
+          };
+
+          if !self.v_resolutions.contains(&resolution) {
+            self.v_resolutions.push(resolution);
+          }
+        } else if x.Type == "Audio" {
+          let lang = x.Language.clone().unwrap_or("?".to_string());
+          if !self.a_languages.contains(&lang) {
+            self.a_languages.push(lang);
+          }
+        } else if x.Type == "Subtitle" {
+          let lang = x.Language.clone().unwrap_or("?".to_string());
+          if !self.s_languages.contains(&lang) {
+            self.s_languages.push(lang);
+          }
+        }
+      }
+    }
+
+    if let Some(runtime) = episode.RunTimeTicks {
+      self.total_runtime += runtime;
+    }
+
+    if let Some(rating) = episode.CommunityRating {
+      self.ratings.push(rating);
+    }
+  }
+
+  fn fields(&self) -> Vec<(String, String, bool)> {
+    let mut fields = Vec::new();
+    fields.push((
+      ":star: — Rating".to_string(),
+      format!(
+        "{:.2}",
+        self.ratings.iter().sum::<f64>() / self.ratings.len() as f64
+      ),
+      true,
+    ));
+    fields.push((
+      ":film_frames: — Runtime".to_string(),
+      format_runtime(self.total_runtime),
+      true,
+    ));
+    fields.push((
+      ":frame_photo: — Resolution".to_string(),
+      self.v_resolutions.join(", "),
+      true,
+    ));
+    fields.push((
+      ":loud_sound: — Languages".to_string(),
+      // 205 is the exact max amount of langs to show (they should all be 3 chars long)
+      self
+        .a_languages
+        .iter()
+        .take(205)
+        .map(|s| s.as_str())
+        .collect::<Vec<&str>>()
+        .join(", "),
+      false,
+    ));
+
+    if !self.s_languages.is_empty() {
+      fields.push((
+        ":notepad_spiral: — Languages".to_string(),
+        self
+          .s_languages
+          .iter()
+          .take(205)
+          .map(|s| s.as_str())
+          .collect::<Vec<&str>>()
+          .join(", "),
+        false,
+      ));
+    }
+    fields
+  }
+}
+
+/// Picks the channel of the most specific matching route (tag, genre, library, type) or falls
+/// back to the channel of the instance.
+fn route(server: &Instance, routes: &[Route], announcement: &Announcement) -> ChannelId {
+  let item = &announcement.item;
+  let matches = |route: &Route| match route.kind.as_str() {
+    "tag" => announcement.subjects().any(|item| {
+      item
+        .Tags
+        .iter()
+        .flatten()
+        .any(|tag| tag.eq_ignore_ascii_case(&route.value))
+    }),
+    "genre" => announcement.subjects().any(|item| {
+      item
+        .Genres
+        .iter()
+        .flatten()
+        .any(|genre| genre.eq_ignore_ascii_case(&route.value))
+    }),
+    "library" => item.LibraryId.as_deref() == Some(route.value.as_str()),
+    "type" => announcement.kind.to_string() == route.value,
+    _ => false,
+  };
+
+  for kind in ["tag", "genre", "library", "type"] {
+    if let Some(route) = routes
+      .iter()
+      .find(|route| route.kind == kind && matches(route))
+    {
+      return ChannelId::new(route.channel_id as u64);
+    }
+  }
+  ChannelId::new(server.channel_id as u64)
+}
+
+/// Posts an announcement to the channel of an instance, or the channel of a matching route.
+///
+/// If the instance has a webhook configured, the message is executed through it with the
+/// configured username and avatar, otherwise it is sent as the bot user. Routed
+/// announcements are always sent as the bot user, since the webhook belongs to the instance's
+/// channel. In forum channels every series gets its own post, which later seasons and episodes
+/// are replied to.
+pub async fn deliver(
+  ctx: &Context,
+  server: &Instance,
+  announcement: &Announcement,
+) -> Result<Message, serenity::Error> {
+  let result = deliver_unrecorded(ctx, server, announcement).await;
+  record_announcement(
+    server.id,
+    announcement,
+    result
+      .as_ref()
+      .ok()
+      .map(|message| (message.channel_id.get() as i64, message.id.get() as i64)),
+  )
+  .await;
+  result
+}
+
+/// Posts an announcement like [`deliver`] without adding it to the history, for test
+/// announcements.
+pub async fn deliver_unrecorded(
+  ctx: &Context,
+  server: &Instance,
+  announcement: &Announcement,
+) -> Result<Message, serenity::Error> {
+  let (channel_id, roles) = destination(server, announcement).await;
+  let item_id = &announcement.item.Id;
+  let payload = Payload {
+    embeds: announcement.embeds(),
+    roles,
+    components: vec![interest_button(
+      server.id,
+      item_id,
+      get_interest(server.id, item_id).await,
+    )],
+  };
+  match channel_id.to_channel(ctx).await {
+    Ok(Channel::Guild(channel)) if channel.kind == ChannelType::Forum => {
+      deliver_forum(ctx, server, &channel, announcement, payload).await
+    },
+    Ok(_) => send(ctx, server, channel_id, payload, Target::Channel).await,
+    Err(why) => Err(why),
+  }
+}
+
+/// Returns the channel an announcement is posted to and the roles it pings.
+pub async fn destination(
+  server: &Instance,
+  announcement: &Announcement,
+) -> (ChannelId, Vec<RoleId>) {
+  let routes = get_routes(server.id).await;
+  (
+    route(server, &routes, announcement),
+    mentions(&get_mentions(server.id).await, announcement),
+  )
+}
+
+/// The button members use to register their interest in an announced item.
+pub fn interest_button(instance_id: i64, item_id: &str, count: i64) -> CreateActionRow {
+  let label = match count {
+    0 => "Want to watch".to_string(),
+    count => format!("Want to watch ({count})"),
+  };
+  CreateActionRow::Buttons(vec![
+    CreateButton::new(format!("interest:{instance_id}:{item_id}"))
+      .label(label)
+      .emoji(ReactionType::Unicode("👀".to_string()))
+      .style(ButtonStyle::Secondary),
+  ])
+}
+
+/// Returns the roles to ping for an announcement.
+fn mentions(mentions: &[Mention], announcement: &Announcement) -> Vec<RoleId> {
+  let item = &announcement.item;
+  let series_id = series_of(item).map(|(id, _)| id);
+  let mut roles: Vec<RoleId> = vec![];
+  for mention in mentions {
+    let matches = match mention.kind.as_str() {
+      "all" => true,
+      "genre" => announcement.subjects().any(|item| {
+        item
+          .Genres
+          .iter()
+          .flatten()
+          .any(|genre| genre.eq_ignore_ascii_case(&mention.value))
+      }),
+      "library" => item.LibraryId.as_deref() == Some(mention.value.as_str()),
+      "series" => series_id.as_deref() == Some(mention.value.as_str()),
+      _ => false,
+    };
+    let role = RoleId::new(mention.role_id as u64);
+    if matches && !roles.contains(&role) {
+      roles.push(role);
+    }
+  }
+  roles
+}
+
+async fn deliver_forum(
+  ctx: &Context,
+  server: &Instance,
+  forum: &GuildChannel,
+  announcement: &Announcement,
+  payload: Payload,
+) -> Result<Message, serenity::Error> {
+  let tags: Vec<ForumTagId> = forum
+    .available_tags
+    .iter()
+    .filter(|tag| {
+      announcement
+        .subjects()
+        .flat_map(|item| item.Genres.iter().flatten())
+        .any(|genre| genre.eq_ignore_ascii_case(&tag.name))
+    })
+    .map(|tag| tag.id)
+    .take(5)
+    .collect();
+
+  let Some((series_id, series_name)) = series_of(&announcement.item) else {
+    let name = announcement.title.clone();
+    return send(ctx, server, forum.id, payload, Target::Post { name, tags }).await;
+  };
+
+  if let Some(thread_id) = get_thread(forum.id.get() as i64, &series_id).await {
+    let thread_id = ChannelId::new(thread_id as u64);
+    match send(
+      ctx,
+      server,
+      forum.id,
+      payload.clone(),
+      Target::Thread(thread_id),
+    )
+    .await
+    {
+      Ok(message) => return Ok(message),
+      Err(why) => {
+        eprintln!("Failed to reply to the post of \"{series_name}\", creating a new one: {why}");
+        remove_thread(forum.id.get() as i64, &series_id).await;
+      },
+    }
+  }
+
+  let message = send(
+    ctx,
+    server,
+    forum.id,
+    payload,
+    Target::Post {
+      name: series_name,
+      tags,
+    },
+  )
+  .await?;
+  // The starter message of a forum post lives in the post itself.
+  set_thread(
+    forum.id.get() as i64,
+    &series_id,
+    message.channel_id.get() as i64,
+  )
+  .await;
+  Ok(message)
+}
+
+/// Returns the id and display name of the series an item belongs to.
+pub fn series_of(item: &Item) -> Option<(String, String)> {
+  match item.Type {
+    Type::Series => Some((item.Id.clone(), item.to_string())),
+    Type::Season | Type::Episode | Type::Special => {
+      Some((item.SeriesId.clone()?, item.SeriesName.clone()?))
+    },
+    Type::Movie => None,
+  }
+}
+
+enum Target {
+  /// The channel itself, or a thread if the channel is one.
+  Channel,
+  /// An existing thread or forum post below the channel.
+  Thread(ChannelId),
+  /// A new forum post.
+  Post { name: String, tags: Vec<ForumTagId> },
+}
+
+/// Posts a message that isn't tied to a single announcement, like a digest, to the channel of
+/// an instance. Forum channels receive a new post with the given title.
+pub async fn post(
+  ctx: &Context,
+  server: &Instance,
+  title: &str,
+  embeds: Vec<CreateEmbed>,
+  components: Vec<CreateActionRow>,
+) -> Result<Message, serenity::Error> {
+  let channel_id = ChannelId::new(server.channel_id as u64);
+  let target = match channel_id.to_channel(ctx).await? {
+    Channel::Guild(channel) if channel.kind == ChannelType::Forum => Target::Post {
+      name: title.to_string(),
+      tags: vec![],
+    },
+    _ => Target::Channel,
+  };
+  let payload = Payload {
+    embeds,
+    components,
+    ..Default::default()
+  };
+  send(ctx, server, channel_id, payload, target).await
+}
+
+/// The content of a message, independent of whether it's sent through a webhook.
+#[derive(Clone, Default)]
+struct Payload {
+  embeds: Vec<CreateEmbed>,
+  /// Roles to ping. No one else can be mentioned by the message.
+  roles: Vec<RoleId>,
+  components: Vec<CreateActionRow>,
+}
+
+impl Payload {
+  fn content(&self) -> String {
+    self
+      .roles
+      .iter()
+      .map(|role| format!("<@&{}>", role))
+      .collect::<Vec<String>>()
+      .join(" ")
+  }
+
+  fn allowed_mentions(&self) -> CreateAllowedMentions {
+    CreateAllowedMentions::new().roles(self.roles.clone())
+  }
+}
+
+async fn send(
+  ctx: &Context,
+  server: &Instance,
+  channel_id: ChannelId,
+  payload: Payload,
+  target: Target,
+) -> Result<Message, serenity::Error> {
+  let webhook_url = server
+    .webhook_url
+    .as_ref()
+    .filter(|_| channel_id.get() == server.channel_id as u64);
+  if let Some(url) = webhook_url {
+    let webhook = Webhook::from_url(&ctx.http, url).await?;
+    let mut builder = ExecuteWebhook::new()
+      .content(payload.content())
+      .allowed_mentions(payload.allowed_mentions())
+      .embeds(payload.embeds);
+    // Only webhooks created by the bot may carry interactive components.
+    if webhook.application_id == ctx.http.application_id() {
+      builder = builder.components(payload.components);
+    }
+    if let Some(username) = &server.webhook_username {
+      builder = builder.username(username);
+    }
+    if let Some(avatar) = &server.webhook_avatar {
+      builder = builder.avatar_url(avatar);
+    }
+    builder = match target {
+      // Webhooks belong to the parent channel of a thread.
+      Target::Channel if webhook.channel_id != Some(channel_id) => builder.in_thread(channel_id),
+      Target::Channel => builder,
+      Target::Thread(thread_id) => builder.in_thread(thread_id),
+      Target::Post { name, tags } => builder
+        .thread_name(name.chars().take(100).collect())
+        .applied_tags(tags),
+    };
+    return webhook
+      .execute(&ctx.http, true, builder)
+      .await?
+      .ok_or(serenity::Error::Other("Webhook didn't return a message"));
+  }
+
+  let message = CreateMessage::new()
+    .content(payload.content())
+    .allowed_mentions(payload.allowed_mentions())
+    .embeds(payload.embeds)
+    .components(payload.components);
+  match target {
+    Target::Channel => channel_id.send_message(ctx, message).await,
+    Target::Thread(thread_id) => thread_id.send_message(ctx, message).await,
+    Target::Post { name, tags } => {
+      // Forum posts are limited to 100 characters.
+      let post = CreateForumPost::new(name.chars().take(100).collect::<String>(), message)
+        .set_applied_tags(tags);
+      let thread = channel_id.create_forum_post(ctx, post).await?;
+      // The starter message of a forum post shares its id with the thread.
+      thread
+        .id
+        .message(ctx, MessageId::new(thread.id.get()))
+        .await
+    },
+  }
+}
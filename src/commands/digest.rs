@@ -0,0 +1,95 @@
+use serenity::all::{CommandDataOption, CommandOptionType, CreateCommand, CreateCommandOption};
+
+use super::{get_string_option, get_subcommand, instance_options, resolve_instance};
+use crate::database::get_database;
+use crate::digest::{Schedule, parse_timezone};
+
+pub async fn run(options: &[CommandDataOption]) -> String {
+  let (subcommand, options) = get_subcommand(options);
+  let server = match resolve_instance(options).await {
+    Ok(server) => server,
+    Err(why) => return why,
+  };
+
+  let database = get_database().await;
+  let content = match subcommand {
+    "enable" => {
+      let Some(schedule) = get_string_option(options, "schedule") else {
+        panic!("Discord returned invalid command options.")
+      };
+      let timezone = get_string_option(options, "timezone").unwrap_or("UTC".to_string());
+      if let Err(why) = Schedule::parse(&schedule) {
+        database.close().await;
+        return why;
+      }
+      if parse_timezone(&timezone).is_none() {
+        database.close().await;
+        return "The timezone has to be a name like \"Europe/Berlin\" or an offset to UTC like \"+02:00\".".to_string();
+      }
+
+      let now = chrono::offset::Utc::now().timestamp();
+      sqlx::query!(
+        "UPDATE FRONT SET Digest_Schedule=?, Digest_Timezone=?, Digest_Last=? WHERE ID=?",
+        schedule,
+        timezone,
+        now,
+        server.id
+      )
+      .execute(&database)
+      .await
+      .expect("update error");
+      format!("New items are now collected and posted on \"{schedule}\" ({timezone}).")
+    },
+    "disable" => {
+      sqlx::query!(
+        "UPDATE FRONT SET Digest_Schedule=NULL, Digest_Timezone=NULL, Digest_Last=NULL WHERE ID=?",
+        server.id
+      )
+      .execute(&database)
+      .await
+      .expect("update error");
+      "New items are now posted right away. Collected items will be posted shortly.".to_string()
+    },
+    _ => panic!("Discord returned invalid command options."),
+  };
+  database.close().await;
+  content
+}
+
+pub fn register() -> CreateCommand {
+  let [channel, instance] = instance_options();
+  CreateCommand::new("digest")
+    .description("Post a summary on a schedule instead of every item right away")
+    .add_option(
+      CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "enable",
+        "Collect new items and post them as one summary",
+      )
+      .add_sub_option(channel.clone())
+      .add_sub_option(
+        CreateCommandOption::new(
+          CommandOptionType::String,
+          "schedule",
+          "minute hour day month weekday, e.g. \"0 18 * * *\" daily or \"0 18 * * 0\" sundays",
+        )
+        .required(true),
+      )
+      .add_sub_option(CreateCommandOption::new(
+        CommandOptionType::String,
+        "timezone",
+        "Name like \"Europe/Berlin\" or offset to UTC like \"+02:00\". Defaults to UTC",
+      ))
+      .add_sub_option(instance.clone()),
+    )
+    .add_option(
+      CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "disable",
+        "Post new items right away again",
+      )
+      .add_sub_option(channel)
+      .add_sub_option(instance),
+    )
+    .dm_permission(false)
+}
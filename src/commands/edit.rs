@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use serenity::all::{
+  ActionRowComponent, CommandInteraction, CommandOptionType, Context, CreateActionRow,
+  CreateCommand, CreateCommandOption, CreateInputText, CreateInteractionResponse,
+  CreateInteractionResponseMessage, CreateModal, EditInteractionResponse, InputTextStyle,
+  ModalInteraction, Permissions,
+};
+use serenity::prelude::TypeMapKey;
+
+use super::init::{Credentials, get_users};
+use super::{ANNOUNCEMENT_CHANNELS, get_channel_option, instance_options, resolve_instance};
+use crate::Instance;
+use crate::database::{copy_seen, get_database, get_instance, get_instances_by_channel};
+
+/// `/edit` commands whose modal hasn't been submitted yet, by the id of the command interaction.
+pub struct PendingEdits;
+
+impl TypeMapKey for PendingEdits {
+  type Value = HashMap<u64, PendingEdit>;
+}
+
+pub struct PendingEdit {
+  created: Instant,
+  instance_id: i64,
+  /// Channel the instance is moved to, if any.
+  channel_id: Option<i64>,
+}
+
+/// Opens a modal with the connection of the instance. The API key is never shown, leaving it
+/// empty keeps the current one.
+pub async fn run(ctx: &Context, command: &CommandInteraction) -> CreateInteractionResponse {
+  let options = &command.data.options;
+  let server = match resolve_instance(options).await {
+    Ok(server) => server,
+    Err(why) => {
+      return CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+          .content(why)
+          .ephemeral(true),
+      );
+    },
+  };
+
+  {
+    let mut data = ctx.data.write().await;
+    let pending = data.entry::<PendingEdits>().or_default();
+    // Modals that were dismissed are never submitted.
+    pending.retain(|_, edit| edit.created.elapsed() < Duration::from_secs(900));
+    pending.insert(
+      command.id.get(),
+      PendingEdit {
+        created: Instant::now(),
+        instance_id: server.id,
+        channel_id: get_channel_option(options, "new_channel"),
+      },
+    );
+  }
+
+  CreateInteractionResponse::Modal(
+    CreateModal::new(
+      format!("edit:{}", command.id),
+      format!("Edit instance {}", server.id),
+    )
+    .components(vec![
+      CreateActionRow::InputText(
+        CreateInputText::new(InputTextStyle::Short, "URL", "url")
+          .value(&server.domain)
+          .min_length(5),
+      ),
+      CreateActionRow::InputText(
+        CreateInputText::new(InputTextStyle::Short, "API key", "api_key")
+          .placeholder("Leave empty to keep the current key")
+          .required(false),
+      ),
+      CreateActionRow::InputText(
+        CreateInputText::new(InputTextStyle::Short, "User", "user")
+          .placeholder("Name or id of the user")
+          .value(server.user_name.as_ref().unwrap_or(&server.user_id)),
+      ),
+    ]),
+  )
+}
+
+/// Re-validates the connection and updates the instance once the modal of [`run`] has been
+/// submitted.
+pub async fn submit(ctx: &Context, modal: &ModalInteraction) {
+  if let Err(why) = modal
+    .create_response(
+      &ctx.http,
+      CreateInteractionResponse::Defer(CreateInteractionResponseMessage::new().ephemeral(true)),
+    )
+    .await
+  {
+    println!("Cannot respond to modal: {}", why);
+    return;
+  }
+
+  let content = match edit(ctx, modal).await {
+    Ok(content) | Err(content) => content,
+  };
+  if let Err(why) = modal
+    .edit_response(&ctx.http, EditInteractionResponse::new().content(content))
+    .await
+  {
+    println!("Cannot edit the edit response: {}", why);
+  }
+}
+
+async fn edit(ctx: &Context, modal: &ModalInteraction) -> Result<String, String> {
+  let id = modal
+    .data
+    .custom_id
+    .split(':')
+    .nth(1)
+    .and_then(|id| id.parse::<u64>().ok());
+  let pending = match id {
+    Some(id) => ctx
+      .data
+      .write()
+      .await
+      .get_mut::<PendingEdits>()
+      .and_then(|pending| pending.remove(&id)),
+    None => None,
+  };
+  let Some(pending) = pending else {
+    return Err("This edit has expired, please run `/edit` again.".to_string());
+  };
+  let Some(server) = get_instance(pending.instance_id).await else {
+    return Err("This instance doesn't exist anymore.".to_string());
+  };
+
+  let mut values: HashMap<&str, String> = HashMap::new();
+  for row in &modal.data.components {
+    for component in &row.components {
+      if let ActionRowComponent::InputText(input) = component {
+        values.insert(
+          input.custom_id.as_str(),
+          input.value.clone().unwrap_or_default(),
+        );
+      }
+    }
+  }
+  let token = values.remove("api_key").unwrap_or_default();
+  let credentials = Credentials {
+    domain: values
+      .remove("url")
+      .unwrap_or_default()
+      .trim()
+      .trim_end_matches('/')
+      .to_string(),
+    token: match token.trim() {
+      "" => server.token.clone(),
+      token => token.to_string(),
+    },
+    // The webhook is kept unless the channel changes.
+    webhook_url: None,
+  };
+  let user = values.remove("user").unwrap_or_default();
+
+  let users = get_users(&credentials).await?;
+  let Some(user) = users.iter().find(|candidate| {
+    candidate.Id == user.trim() || candidate.Name.eq_ignore_ascii_case(user.trim())
+  }) else {
+    return Err(format!(
+      "The user could not be found. Available users: {}",
+      users
+        .iter()
+        .map(|user| user.Name.clone())
+        .collect::<Vec<String>>()
+        .join(", ")
+    ));
+  };
+
+  let channel_id = pending.channel_id.unwrap_or(server.channel_id);
+  if get_instances_by_channel(channel_id)
+    .await
+    .iter()
+    .any(|instance| instance.id != server.id && instance.user_id == user.Id)
+  {
+    return Err("This UserID has already been added to the channel.".to_string());
+  }
+
+  let mut changes: Vec<String> = vec![];
+  let mut updated = Instance {
+    domain: credentials.domain.clone(),
+    token: credentials.token.clone(),
+    user_id: user.Id.clone(),
+    user_name: Some(user.Name.clone()),
+    channel_id,
+    ..server.clone()
+  };
+  if updated.domain != server.domain {
+    changes.push("URL".to_string());
+  }
+  if updated.token != server.token {
+    changes.push("API key".to_string());
+  }
+  if updated.user_id != server.user_id {
+    // The seen items move along, so the library of the new user isn't announced.
+    copy_seen(&server.user_id, &updated.user_id).await;
+    changes.push(format!("user ({})", user.Name));
+  }
+  if updated.channel_id != server.channel_id {
+    // Webhooks belong to a single channel.
+    updated.webhook_url = None;
+    updated.guild_id = modal.guild_id.map(|guild_id| guild_id.get() as i64);
+    changes.push(format!("channel (<#{}>)", channel_id));
+    if server.webhook_url.is_some() {
+      changes.push("webhook removed, rerun `/init` to create a new one".to_string());
+    }
+  }
+  if changes.is_empty() {
+    return Ok("Nothing has changed.".to_string());
+  }
+
+  let stored_token = crate::crypto::encrypt(&updated.token);
+  let stored_webhook_url = updated.webhook_url.as_deref().map(crate::crypto::encrypt);
+  let database = get_database().await;
+  sqlx::query!(
+    "UPDATE FRONT SET Domain=?, Token=?, UserID=?, User_Name=?, Channel_ID=?, Guild_ID=?, Webhook_URL=? WHERE ID=?",
+    updated.domain,
+    stored_token,
+    updated.user_id,
+    updated.user_name,
+    updated.channel_id,
+    updated.guild_id,
+    stored_webhook_url,
+    updated.id
+  )
+  .execute(&database)
+  .await
+  .expect("update error");
+  database.close().await;
+
+  Ok(format!(
+    "Successfully updated instance {}: {}.",
+    server.id,
+    changes.join(", ")
+  ))
+}
+
+pub fn register() -> CreateCommand {
+  let [channel, instance] = instance_options();
+  CreateCommand::new("edit")
+    .description("Change the server, API key, user or channel of an instance")
+    .add_option(channel)
+    .add_option(
+      CreateCommandOption::new(
+        CommandOptionType::Channel,
+        "new_channel",
+        "Move the instance to another channel",
+      )
+      .channel_types(ANNOUNCEMENT_CHANNELS.to_vec()),
+    )
+    .add_option(instance)
+    .default_member_permissions(Permissions::ADMINISTRATOR)
+}
@@ -0,0 +1,141 @@
+use serenity::all::{CommandDataOption, CommandOptionType, CreateCommand, CreateCommandOption};
+
+use super::{
+  get_integer_option, get_string_option, get_subcommand, instance_options, resolve_instance,
+};
+use crate::Instance;
+use crate::database::{get_database, get_filters};
+
+pub async fn run(options: &[CommandDataOption]) -> String {
+  let (subcommand, options) = get_subcommand(options);
+  let server = match resolve_instance(options).await {
+    Ok(server) => server,
+    Err(why) => return why,
+  };
+
+  match subcommand {
+    "add" => add(&server, options).await,
+    "list" => list(&server).await,
+    "remove" => remove(&server, options).await,
+    _ => panic!("Discord returned invalid command options."),
+  }
+}
+
+async fn add(server: &Instance, options: &[CommandDataOption]) -> String {
+  let (Some(kind), Some(value)) = (
+    get_string_option(options, "kind"),
+    get_string_option(options, "value"),
+  ) else {
+    panic!("Discord returned invalid command options.")
+  };
+  let value = value.trim().to_string();
+  if kind == "score" && value.parse::<f64>().is_err() {
+    return "The minimum rating has to be a number, e.g. \"6.5\".".to_string();
+  }
+
+  let database = get_database().await;
+  sqlx::query!(
+    "INSERT INTO FILTERS (Instance_ID, Kind, Value) VALUES (?1, ?2, ?3)",
+    server.id,
+    kind,
+    value
+  )
+  .execute(&database)
+  .await
+  .expect("insert error");
+  database.close().await;
+
+  "Successfully added filter.".to_string()
+}
+
+async fn list(server: &Instance) -> String {
+  let filters = get_filters(server.id).await;
+  if filters.is_empty() {
+    return "No filters. Everything is announced.".to_string();
+  }
+
+  filters
+    .iter()
+    .map(|filter| {
+      let description = match filter.kind.as_str() {
+        "rating" => format!("Skip items rated \"{}\"", filter.value),
+        "tag" => format!("Skip items tagged \"{}\"", filter.value),
+        "genre" => format!("Skip items of the genre \"{}\"", filter.value),
+        "score" => format!("Skip items with a rating below {}", filter.value),
+        "audio" => format!("Skip items without \"{}\" audio", filter.value),
+        _ => format!("{} \"{}\"", filter.kind, filter.value),
+      };
+      format!("`{}` {}", filter.id, description)
+    })
+    .collect::<Vec<String>>()
+    .join("\n")
+}
+
+async fn remove(server: &Instance, options: &[CommandDataOption]) -> String {
+  let Some(id) = get_integer_option(options, "id") else {
+    panic!("Discord returned invalid command options.")
+  };
+
+  let database = get_database().await;
+  let result = sqlx::query!(
+    "DELETE FROM FILTERS WHERE ID=? AND Instance_ID=?",
+    id,
+    server.id
+  )
+  .execute(&database)
+  .await
+  .expect("delete error");
+  database.close().await;
+
+  if result.rows_affected() == 0 {
+    "Filter could not be found.".to_string()
+  } else {
+    "Successfully removed filter.".to_string()
+  }
+}
+
+pub fn register() -> CreateCommand {
+  let [channel, instance] = instance_options();
+  CreateCommand::new("filter")
+    .description("Skip announcements by age rating, genre, tag, rating or audio language")
+    .add_option(
+      CreateCommandOption::new(CommandOptionType::SubCommand, "add", "Add a filter")
+        .add_sub_option(channel.clone())
+        .add_sub_option(
+          CreateCommandOption::new(CommandOptionType::String, "kind", "What to filter")
+            .add_string_choice(
+              "Skip age ratings, comma separated (e.g. R,NC-17,TV-MA)",
+              "rating",
+            )
+            .add_string_choice("Skip tag", "tag")
+            .add_string_choice("Skip genre", "genre")
+            .add_string_choice("Minimum community rating", "score")
+            .add_string_choice("Required audio language (e.g. eng)", "audio")
+            .required(true),
+        )
+        .add_sub_option(
+          CreateCommandOption::new(
+            CommandOptionType::String,
+            "value",
+            "Age rating, tag, genre, rating or language",
+          )
+          .required(true),
+        )
+        .add_sub_option(instance.clone()),
+    )
+    .add_option(
+      CreateCommandOption::new(CommandOptionType::SubCommand, "list", "List all filters")
+        .add_sub_option(channel.clone())
+        .add_sub_option(instance.clone()),
+    )
+    .add_option(
+      CreateCommandOption::new(CommandOptionType::SubCommand, "remove", "Remove a filter")
+        .add_sub_option(channel)
+        .add_sub_option(
+          CreateCommandOption::new(CommandOptionType::Integer, "id", "Id of the filter")
+            .required(true),
+        )
+        .add_sub_option(instance),
+    )
+    .dm_permission(false)
+}
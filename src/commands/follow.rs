@@ -0,0 +1,73 @@
+use serenity::all::{
+  CommandDataOption, CommandOptionType, Context, CreateAutocompleteResponse, CreateCommand,
+  CreateCommandOption, GuildId,
+};
+
+use super::{find_item, get_string_option, item_choices};
+use crate::database::{get_database, get_instances_by_guild};
+use crate::follows::sync_roles;
+
+pub async fn run(
+  ctx: &Context,
+  guild_id: Option<GuildId>,
+  user_id: i64,
+  options: &[CommandDataOption],
+) -> String {
+  let Some(guild_id) = guild_id else {
+    return "Series can only be followed within a server.".to_string();
+  };
+  let Some(value) = get_string_option(options, "series") else {
+    panic!("Discord returned invalid command options.")
+  };
+  let instances = get_instances_by_guild(guild_id.get() as i64).await;
+  if instances.is_empty() {
+    return "This server doesn't announce any media yet.".to_string();
+  }
+
+  let Some((server, series)) = find_item(&instances, &value, "Series").await else {
+    return "Series could not be found.".to_string();
+  };
+
+  let database = get_database().await;
+  let series_name = series.to_string();
+  let result = sqlx::query!(
+    "INSERT OR IGNORE INTO FOLLOWS (User_ID, Instance_ID, Series_ID, Series_Name) VALUES (?1, ?2, ?3, ?4)",
+    user_id,
+    server.id,
+    series.Id,
+    series_name
+  )
+  .execute(&database)
+  .await
+  .expect("insert error");
+  database.close().await;
+
+  if result.rows_affected() == 0 {
+    return format!("You're already following \"{series_name}\".");
+  }
+  let mut response =
+    format!("You'll now receive a direct message for new episodes of \"{series_name}\".");
+  if !sync_roles(ctx, server, &series.Id, user_id, true)
+    .await
+    .is_empty()
+  {
+    response.push_str("\nThe follower role of the series couldn't be given to you.");
+  }
+  response
+}
+
+/// Suggests series of the server's instances matching what has been typed so far.
+pub async fn autocomplete(guild_id: Option<GuildId>, value: &str) -> CreateAutocompleteResponse {
+  item_choices(guild_id, value, "Series").await
+}
+
+pub fn register() -> CreateCommand {
+  CreateCommand::new("follow")
+    .description("Get a direct message for new episodes of a series")
+    .add_option(
+      CreateCommandOption::new(CommandOptionType::String, "series", "Series to follow")
+        .set_autocomplete(true)
+        .required(true),
+    )
+    .dm_permission(false)
+}
@@ -0,0 +1,45 @@
+use serenity::all::{
+  CommandDataOption, CommandDataOptionValue, CommandOptionType, CreateCommand, CreateCommandOption,
+};
+
+use super::get_option;
+use crate::database::{get_dms_enabled, get_follows, set_dms_enabled};
+
+pub async fn run(user_id: i64, options: &[CommandDataOption]) -> String {
+  let mut response = String::new();
+  if let Some(CommandDataOptionValue::Boolean(enabled)) = get_option(options, "dms") {
+    set_dms_enabled(user_id, *enabled).await;
+    response.push_str(if *enabled {
+      "Direct messages have been enabled.\n"
+    } else {
+      "Direct messages have been disabled. Your follows are kept.\n"
+    });
+  }
+
+  let follows = get_follows(user_id).await;
+  if follows.is_empty() {
+    response.push_str("You aren't following any series. Use `/follow` to add one.");
+    return response;
+  }
+  if !get_dms_enabled(user_id).await {
+    response.push_str("Direct messages are disabled, use `/following dms:True` to enable them.\n");
+  }
+  response.push_str(
+    &follows
+      .iter()
+      .map(|follow| format!("- {}", follow.series_name))
+      .collect::<Vec<String>>()
+      .join("\n"),
+  );
+  response
+}
+
+pub fn register() -> CreateCommand {
+  CreateCommand::new("following")
+    .description("List followed series and turn direct messages on or off")
+    .add_option(CreateCommandOption::new(
+      CommandOptionType::Boolean,
+      "dms",
+      "Whether to receive direct messages for followed series",
+    ))
+}
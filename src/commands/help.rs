@@ -1,20 +1,35 @@
-use serenity::all::{CommandDataOption, CreateCommand, Permissions};
+use serenity::all::{CommandDataOption, CreateCommand};
 
-pub async fn run(_options: &[CommandDataOption]) -> String {
-  "```\
-[JellyCord]
+use super::{Access, CATALOG};
 
-Commands:
-  \"init\"  - Initialize current channel and setup jellyfin connection
-  \"reset\" - Break jellyfin connection for the current channel
-  \"pause\" - Don't check for any updates, regarding this channel | TOGGLE
-  \"ping\"  - Check if the bot is still running
-```"
-    .to_string()
+pub async fn run(_options: &[CommandDataOption]) -> String {
+  let width = CATALOG
+    .iter()
+    .map(|command| command.name.len())
+    .max()
+    .unwrap_or(0);
+  let mut help = "```\n[JellyCord]\n".to_string();
+  for (access, title) in [
+    (Access::Admin, "Administrator commands"),
+    (Access::Manager, "Manager commands"),
+    (Access::Member, "Member commands"),
+  ] {
+    help.push_str(&format!("\n{title}:\n"));
+    for command in CATALOG.iter().filter(|command| command.access == access) {
+      // The description shown in discord's command picker is the only one kept.
+      let registered = serde_json::to_value((command.register)()).unwrap_or_default();
+      help.push_str(&format!(
+        "  {:<width$} - {}\n",
+        format!("\"{}\"", command.name),
+        registered["description"].as_str().unwrap_or_default(),
+        width = width + 2
+      ));
+    }
+  }
+  help.push_str("```");
+  help
 }
 
 pub fn register() -> CreateCommand {
-  CreateCommand::new("help")
-    .description("Small description of available commands")
-    .default_member_permissions(Permissions::ADMINISTRATOR)
+  CreateCommand::new("help").description("List the commands of the bot")
 }
@@ -0,0 +1,238 @@
+use serenity::all::{
+  ButtonStyle, ChannelId, CommandDataOption, CommandOptionType, ComponentInteraction,
+  ComponentInteractionDataKind, Context, CreateActionRow, CreateButton, CreateCommand,
+  CreateCommandOption, CreateEmbed, CreateEmbedFooter, CreateInteractionResponse,
+  CreateInteractionResponseMessage, CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption,
+  EditInteractionResponse, GuildId, MessageId,
+};
+
+use super::get_integer_option;
+use crate::HistoryEntry;
+use crate::announcement::{self, Announcement};
+use crate::database::{
+  get_history, get_history_entry, get_instance, set_message_status, set_repost,
+};
+
+const PAGE_SIZE: usize = 10;
+
+pub async fn run(
+  guild_id: Option<GuildId>,
+  options: &[CommandDataOption],
+) -> CreateInteractionResponseMessage {
+  let message = CreateInteractionResponseMessage::new();
+  let Some(guild_id) = guild_id else {
+    return message.content("The history can only be shown within a server.");
+  };
+  let days = get_integer_option(options, "days").unwrap_or(7);
+  let (embed, components) = page(guild_id, days, 0).await;
+  message.embed(embed).components(components)
+}
+
+/// Handles the buttons and menus of `/history`: switching pages, posting an announcement again
+/// and deleting it.
+pub async fn interact(ctx: &Context, component: &ComponentInteraction) {
+  let mut parts = component.data.custom_id.split(':').skip(1);
+  let (Some(Ok(days)), Some(Ok(page_index)), action) = (
+    parts.next().map(str::parse::<i64>),
+    parts.next().map(str::parse::<usize>),
+    parts.next(),
+  ) else {
+    panic!("Discord returned an invalid custom id.")
+  };
+  let Some(guild_id) = component.guild_id else {
+    return;
+  };
+
+  let Some(action) = action else {
+    let (embed, components) = page(guild_id, days, page_index).await;
+    let builder = CreateInteractionResponse::UpdateMessage(
+      CreateInteractionResponseMessage::new()
+        .embed(embed)
+        .components(components),
+    );
+    if let Err(why) = component.create_response(&ctx.http, builder).await {
+      println!("Cannot respond to component: {}", why);
+    }
+    return;
+  };
+
+  // Posting can take a moment, so the menu is acknowledged first.
+  if let Err(why) = component
+    .create_response(&ctx.http, CreateInteractionResponse::Acknowledge)
+    .await
+  {
+    println!("Cannot respond to component: {}", why);
+    return;
+  }
+  let ComponentInteractionDataKind::StringSelect { values } = &component.data.kind else {
+    panic!("Discord returned an invalid component.")
+  };
+  let Some(Ok(id)) = values.first().map(|value| value.parse::<i64>()) else {
+    panic!("Discord returned an invalid component.")
+  };
+  let content = match get_history_entry(guild_id.get() as i64, id).await {
+    None => "This announcement doesn't exist anymore.".to_string(),
+    Some(entry) if action == "repost" => repost(ctx, guild_id, &entry).await,
+    Some(entry) => delete(ctx, &entry).await,
+  };
+
+  let (embed, components) = page(guild_id, days, page_index).await;
+  let builder = EditInteractionResponse::new()
+    .content(content)
+    .embed(embed)
+    .components(components);
+  if let Err(why) = component.edit_response(&ctx.http, builder).await {
+    println!("Cannot edit the history: {}", why);
+  }
+}
+
+async fn repost(ctx: &Context, guild_id: GuildId, entry: &HistoryEntry) -> String {
+  let Some(Ok(announcement)) = entry
+    .announcement
+    .as_deref()
+    .map(serde_json::from_str::<Announcement>)
+  else {
+    return format!(
+      "\"{}\" was announced before its content was kept, so it can't be posted again.",
+      entry.title
+    );
+  };
+  let Some(server) = get_instance(entry.instance_id).await else {
+    return "The instance of this announcement doesn't exist anymore.".to_string();
+  };
+  match announcement::deliver(ctx, &server, &announcement).await {
+    Ok(message) => {
+      set_repost(message.id.get() as i64).await;
+      format!(
+        "Posted \"{}\" again: https://discord.com/channels/{}/{}/{}",
+        entry.title, guild_id, message.channel_id, message.id
+      )
+    },
+    Err(why) => format!("Failed to post \"{}\" again: {why}", entry.title),
+  }
+}
+
+async fn delete(ctx: &Context, entry: &HistoryEntry) -> String {
+  let (Some(channel_id), Some(message_id), "posted") =
+    (entry.channel_id, entry.message_id, entry.status.as_str())
+  else {
+    return format!("\"{}\" isn't posted, so it can't be deleted.", entry.title);
+  };
+  match ChannelId::new(channel_id as u64)
+    .delete_message(&ctx.http, MessageId::new(message_id as u64))
+    .await
+  {
+    Ok(()) => {
+      set_message_status(message_id, "deleted").await;
+      format!("Deleted the announcement of \"{}\".", entry.title)
+    },
+    Err(why) => format!(
+      "Failed to delete the announcement of \"{}\": {why}",
+      entry.title
+    ),
+  }
+}
+
+async fn page(
+  guild_id: GuildId,
+  days: i64,
+  page_index: usize,
+) -> (CreateEmbed, Vec<CreateActionRow>) {
+  let since = chrono::offset::Utc::now().timestamp() - days * 86400;
+  let history = get_history(guild_id.get() as i64, since).await;
+  let pages = history.len().div_ceil(PAGE_SIZE).max(1);
+  let page_index = page_index.min(pages - 1);
+  let entries: Vec<&HistoryEntry> = history
+    .iter()
+    .skip(page_index * PAGE_SIZE)
+    .take(PAGE_SIZE)
+    .collect();
+
+  let mut embed = CreateEmbed::new()
+    .title(format!("Announcements of the last {days} days"))
+    .footer(CreateEmbedFooter::new(format!(
+      "Page {} of {} - {} announcements",
+      page_index + 1,
+      pages,
+      history.len()
+    )));
+  if history.is_empty() {
+    embed = embed.description("Nothing has been announced in this time.");
+  }
+  for entry in &entries {
+    let status = match (entry.status.as_str(), entry.channel_id, entry.message_id) {
+      ("posted", Some(channel_id), Some(message_id)) => format!(
+        "[Posted](https://discord.com/channels/{}/{}/{})",
+        guild_id, channel_id, message_id
+      ),
+      ("deleted", ..) => "Deleted".to_string(),
+      _ => "Failed".to_string(),
+    };
+    embed = embed.field(
+      format!("{} - {}", entry.id, entry.title)
+        .chars()
+        .take(256)
+        .collect::<String>(),
+      format!("{} - <t:{}:R> - {}", entry.kind, entry.created, status),
+      false,
+    );
+  }
+
+  let mut components = vec![];
+  if pages > 1 {
+    components.push(CreateActionRow::Buttons(vec![
+      CreateButton::new(format!("history:{}:{}", days, page_index.saturating_sub(1)))
+        .label("Previous")
+        .style(ButtonStyle::Secondary)
+        .disabled(page_index == 0),
+      CreateButton::new(format!("history:{}:{}", days, page_index + 1))
+        .label("Next")
+        .style(ButtonStyle::Secondary)
+        .disabled(page_index + 1 >= pages),
+    ]));
+  }
+  for (action, placeholder) in [
+    ("repost", "Post an announcement again"),
+    ("delete", "Delete an announcement"),
+  ] {
+    let options: Vec<CreateSelectMenuOption> = entries
+      .iter()
+      .filter(|entry| action == "repost" || entry.status == "posted")
+      .map(|entry| {
+        CreateSelectMenuOption::new(
+          format!("{} - {}", entry.id, entry.title)
+            .chars()
+            .take(100)
+            .collect::<String>(),
+          entry.id.to_string(),
+        )
+      })
+      .collect();
+    if options.is_empty() {
+      continue;
+    }
+    components.push(CreateActionRow::SelectMenu(
+      CreateSelectMenu::new(
+        format!("history:{}:{}:{}", days, page_index, action),
+        CreateSelectMenuKind::String { options },
+      )
+      .placeholder(placeholder),
+    ));
+  }
+  (embed, components)
+}
+
+pub fn register() -> CreateCommand {
+  CreateCommand::new("history")
+    .description("Browse past announcements, post them again or delete them")
+    .add_option(
+      CreateCommandOption::new(
+        CommandOptionType::Integer,
+        "days",
+        "How far back to look (7 days by default)",
+      )
+      .min_int_value(1)
+      .max_int_value(365),
+    )
+    .dm_permission(false)
+}
@@ -1,187 +1,588 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
 use reqwest::Client;
 use serenity::all::{
-  ChannelType, CommandDataOption, CommandDataOptionValue, CommandOptionType, CreateCommand,
-  CreateCommandOption, Permissions,
+  ActionRowComponent, Builder, Channel, ChannelId, ChannelType, CommandDataOption,
+  CommandDataOptionValue, CommandInteraction, CommandOptionType, ComponentInteraction,
+  ComponentInteractionDataKind, Context, CreateActionRow, CreateCommand, CreateCommandOption,
+  CreateInputText, CreateInteractionResponse, CreateInteractionResponseMessage, CreateModal,
+  CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, CreateWebhook,
+  EditInteractionResponse, GuildId, InputTextStyle, ModalInteraction, Permissions, Webhook,
 };
+use serenity::prelude::TypeMapKey;
 
-use crate::{Instance, UserList};
+use super::{ANNOUNCEMENT_CHANNELS, get_channel_option, get_option, get_string_option, libraries};
+use crate::database::{
+  get_database, get_instances_by_channel, get_library_by_user, is_seen_kept, mark_seen,
+  restore_history,
+};
+use crate::{Instance, UserList, get_library_items, get_user};
 
-pub async fn run(options: &[CommandDataOption]) -> String {
-  let channel_id = match options.get(0).unwrap().value {
-    CommandDataOptionValue::Channel(integer) => integer.get() as i64,
-    _ => {
-      panic!("Discord returned invalid command options.")
-    },
+/// `/init` commands that haven't been completed yet, by the id of the command interaction.
+pub struct PendingSetups;
+
+impl TypeMapKey for PendingSetups {
+  type Value = HashMap<u64, PendingSetup>;
+}
+
+pub struct PendingSetup {
+  created: Instant,
+  options: Vec<CommandDataOption>,
+  /// Set once the modal has been submitted and the server accepted the API key.
+  credentials: Option<Credentials>,
+}
+
+/// The validated values entered into the modal of [`run`].
+pub(super) struct Credentials {
+  pub domain: String,
+  pub token: String,
+  /// Existing webhook to post through. Its URL contains a token, so it's entered in the modal.
+  pub webhook_url: Option<String>,
+}
+
+/// Opens the modal asking for the credentials of the server. They aren't slash command options,
+/// so they never show up in the channel.
+pub async fn run(ctx: &Context, command: &CommandInteraction) -> CreateInteractionResponse {
+  {
+    let mut data = ctx.data.write().await;
+    let pending = data.entry::<PendingSetups>().or_default();
+    // Modals that were dismissed are never submitted.
+    pending.retain(|_, setup| setup.created.elapsed() < Duration::from_secs(900));
+    pending.insert(
+      command.id.get(),
+      PendingSetup {
+        created: Instant::now(),
+        options: command.data.options.clone(),
+        credentials: None,
+      },
+    );
+  }
+
+  let input = |label: &str, id: &str, placeholder: &str, min_length: u16| {
+    CreateInputText::new(InputTextStyle::Short, label, id)
+      .placeholder(placeholder)
+      .min_length(min_length)
   };
-  let url = match &options.get(1).unwrap().value {
-    CommandDataOptionValue::String(text) => text,
-    _ => {
-      panic!("Discord returned invalid command options.")
-    },
+  CreateInteractionResponse::Modal(
+    CreateModal::new(format!("init:{}", command.id), "Connect to Jellyfin").components(vec![
+      CreateActionRow::InputText(input("URL", "url", "https://jellyfin.yourdomain.com", 5)),
+      CreateActionRow::InputText(input("API key", "api_key", "Dashboard > API Keys", 10)),
+      CreateActionRow::InputText(
+        input(
+          "User (optional)",
+          "user",
+          "Name or id of the user. Pick from a list if empty",
+          1,
+        )
+        .required(false),
+      ),
+      CreateActionRow::InputText(
+        input(
+          "Webhook URL (optional)",
+          "webhook_url",
+          "Post through an existing webhook of the channel",
+          1,
+        )
+        .required(false),
+      ),
+    ]),
+  )
+}
+
+/// Validates the credentials once the modal of [`run`] has been submitted, then either continues
+/// with the entered user or offers a list of the server's users.
+///
+/// Indexing the library takes far longer than discord waits for a response, so the response is
+/// deferred and edited as the setup progresses.
+pub async fn submit(ctx: &Context, modal: &ModalInteraction) {
+  if let Err(why) = modal
+    .create_response(
+      &ctx.http,
+      CreateInteractionResponse::Defer(CreateInteractionResponseMessage::new().ephemeral(true)),
+    )
+    .await
+  {
+    println!("Cannot respond to modal: {}", why);
+    return;
+  }
+  let progress = Progress {
+    ctx,
+    token: &modal.token,
   };
-  let token = match &options.get(2).unwrap().value {
-    CommandDataOptionValue::String(text) => text,
-    _ => {
-      panic!("Discord returned invalid command options.")
-    },
+  let Some(id) = setup_id(&modal.data.custom_id) else {
+    panic!("Discord returned an invalid custom id.")
+  };
+  if !ctx
+    .data
+    .read()
+    .await
+    .get::<PendingSetups>()
+    .is_some_and(|pending| pending.contains_key(&id))
+  {
+    progress.finish(EXPIRED, vec![]).await;
+    return;
+  }
+
+  let mut values: HashMap<&str, String> = HashMap::new();
+  for row in &modal.data.components {
+    for component in &row.components {
+      if let ActionRowComponent::InputText(input) = component {
+        values.insert(
+          input.custom_id.as_str(),
+          input.value.clone().unwrap_or_default(),
+        );
+      }
+    }
+  }
+  let user = values.remove("user").unwrap_or_default();
+
+  progress.update("Connecting to the server…").await;
+  let credentials = Credentials {
+    domain: values
+      .remove("url")
+      .unwrap_or_default()
+      .trim()
+      .trim_end_matches('/')
+      .to_string(),
+    token: values
+      .remove("api_key")
+      .unwrap_or_default()
+      .trim()
+      .to_string(),
+    webhook_url: values
+      .remove("webhook_url")
+      .map(|url| url.trim().to_string())
+      .filter(|url| !url.is_empty()),
   };
-  let username = match &options.get(3).unwrap().value {
-    CommandDataOptionValue::String(text) => text,
-    _ => {
-      panic!("Discord returned invalid command options.")
+  let users = match get_users(&credentials).await {
+    Ok(users) => users,
+    Err(why) => {
+      progress.finish(why, vec![]).await;
+      return;
     },
   };
 
-  let database = sqlx::sqlite::SqlitePoolOptions::new()
-    .max_connections(5)
-    .connect_with(
-      sqlx::sqlite::SqliteConnectOptions::new()
-        .filename("jellycord.sqlite")
-        .create_if_missing(true),
+  let user = user.trim();
+  let selected = users
+    .iter()
+    .find(|candidate| candidate.Id == user || candidate.Name.eq_ignore_ascii_case(user));
+  if let Some(selected) = selected {
+    let user_id = selected.Id.clone();
+    let Some(setup) = take_setup(ctx, id).await else {
+      progress.finish(EXPIRED, vec![]).await;
+      return;
+    };
+    complete(
+      ctx,
+      modal.guild_id,
+      &setup.options,
+      &credentials,
+      user_id,
+      &progress,
     )
+    .await;
+    return;
+  }
+
+  if let Some(setup) = ctx
+    .data
+    .write()
+    .await
+    .get_mut::<PendingSetups>()
+    .and_then(|pending| pending.get_mut(&id))
+  {
+    setup.credentials = Some(credentials);
+  }
+  let mut content = String::new();
+  if !user.is_empty() {
+    content.push_str(&format!("The user \"{user}\" could not be found. "));
+  }
+  content.push_str("Choose the user whose libraries will be announced:");
+  if users.len() > 25 {
+    content.push_str("\nOnly the first 25 users are listed, run `/init` again and enter the name or id of another one.");
+  }
+  progress.finish(content, vec![user_menu(id, &users)]).await;
+}
+
+/// Completes the setup with the user chosen from the menu of [`submit`].
+pub async fn select(ctx: &Context, component: &ComponentInteraction) {
+  if let Err(why) = component
+    .create_response(&ctx.http, CreateInteractionResponse::Acknowledge)
+    .await
+  {
+    println!("Cannot respond to component: {}", why);
+    return;
+  }
+  let progress = Progress {
+    ctx,
+    token: &component.token,
+  };
+  let Some(id) = setup_id(&component.data.custom_id) else {
+    panic!("Discord returned an invalid custom id.")
+  };
+  let ComponentInteractionDataKind::StringSelect { values } = &component.data.kind else {
+    panic!("Discord returned an invalid component.")
+  };
+  let Some(user_id) = values.first() else {
+    return;
+  };
+  let Some(PendingSetup {
+    options,
+    credentials: Some(credentials),
+    ..
+  }) = take_setup(ctx, id).await
+  else {
+    progress.finish(EXPIRED, vec![]).await;
+    return;
+  };
+  complete(
+    ctx,
+    component.guild_id,
+    &options,
+    &credentials,
+    user_id.clone(),
+    &progress,
+  )
+  .await;
+}
+
+const EXPIRED: &str = "This setup has expired, please run `/init` again.";
+
+fn setup_id(custom_id: &str) -> Option<u64> {
+  custom_id.split(':').nth(1)?.parse::<u64>().ok()
+}
+
+async fn take_setup(ctx: &Context, id: u64) -> Option<PendingSetup> {
+  ctx
+    .data
+    .write()
     .await
-    .expect("Couldn't connect to database");
+    .get_mut::<PendingSetups>()?
+    .remove(&id)
+}
+
+/// Lists the users of the server, flagging administrators and disabled accounts.
+fn user_menu(id: u64, users: &[UserList]) -> CreateActionRow {
+  let options = users
+    .iter()
+    .take(25)
+    .map(|user| {
+      let mut flags: Vec<&str> = vec![];
+      if let Some(policy) = &user.Policy {
+        if policy.IsAdministrator {
+          flags.push("Administrator");
+        }
+        if policy.IsDisabled {
+          flags.push("Disabled");
+        }
+      }
+      let mut option =
+        CreateSelectMenuOption::new(user.Name.chars().take(100).collect::<String>(), &user.Id);
+      if !flags.is_empty() {
+        option = option.description(flags.join(", "));
+      }
+      option
+    })
+    .collect();
+  CreateActionRow::SelectMenu(
+    CreateSelectMenu::new(
+      format!("init:{id}"),
+      CreateSelectMenuKind::String { options },
+    )
+    .placeholder("Select a user"),
+  )
+}
 
-  let domain = url.trim_end_matches('/').to_string();
-  let client = Client::new();
-  let users_request = client
-    .get(format!("{}/Users?api_key={}", &domain, &token))
+/// Fetches the users of the server, which also checks the URL and the API key.
+pub(super) async fn get_users(credentials: &Credentials) -> Result<Vec<UserList>, String> {
+  let users_request = Client::new()
+    .get(format!(
+      "{}/Users?api_key={}",
+      &credentials.domain, &credentials.token
+    ))
+    .timeout(Duration::from_secs(30))
     .send()
     .await;
-  if users_request.is_err() {
-    database.close().await;
-    return "The URL you've entered, seems to be of invalid format?\n- \"https://emby.yourdomain.com\"".to_string();
-  }
-
-  let users: Result<Vec<UserList>, String> = match users_request {
-    Ok(ok) => {
-      let serde_attempt = serde_json::from_str::<Vec<UserList>>(&ok.text().await.unwrap());
-      match serde_attempt {
-        Ok(ok) => Ok(ok),
-        Err(_) => {
-          database.close().await;
-          return "The request to retrieve available users failed.\nThis is likely due to an incorrect response or invalid api_key. Is this really a supported mediaserver?".to_string();
-        },
-      }
+  match users_request {
+    Ok(ok) => match serde_json::from_str::<Vec<UserList>>(&ok.text().await.unwrap_or_default()) {
+      Ok(ok) => Ok(ok),
+      Err(_) => Err("The request to retrieve available users failed.\nThis is likely due to an incorrect response or invalid api_key. Is this really a supported mediaserver?".to_string()),
     },
-    Err(err) => {
-      database.close().await;
-      return format!(
-        "The request to retrieve available users failed. Try to add \"https://\"\nError: {err}"
-      );
+    Err(err) => Err(format!(
+      "The URL you've entered seems to be of invalid format or the server can't be reached. Try to add \"https://\"\n- \"https://emby.yourdomain.com\"\nError: {}",
+      err.without_url()
+    )),
+  }
+}
+
+async fn complete(
+  ctx: &Context,
+  guild_id: Option<GuildId>,
+  options: &[CommandDataOption],
+  credentials: &Credentials,
+  user_id: String,
+  progress: &Progress<'_>,
+) {
+  match setup(ctx, guild_id, options, credentials, user_id, progress).await {
+    Ok((server, summary)) => match libraries::menu(&server, 1).await {
+      Ok(components) => {
+        progress
+          .finish(
+            format!("{summary}\nOptionally limit the announcements to some libraries:"),
+            components,
+          )
+          .await
+      },
+      Err(_) => progress.finish(summary, vec![]).await,
     },
-  };
+    Err(why) => progress.finish(why, vec![]).await,
+  }
+}
+
+/// Reports the steps of the setup through the deferred response of the interaction.
+struct Progress<'a> {
+  ctx: &'a Context,
+  token: &'a str,
+}
+
+impl Progress<'_> {
+  async fn update(&self, content: impl Into<String>) {
+    self
+      .edit(EditInteractionResponse::new().content(content))
+      .await;
+  }
 
-  let mut user_id_raw: Option<String> = None;
-  for user in users.as_ref().unwrap().clone().into_iter() {
-    if user.Name.to_lowercase() == username.to_lowercase().trim() {
-      user_id_raw = Some(user.Id)
+  async fn finish(&self, content: impl Into<String>, components: Vec<CreateActionRow>) {
+    self
+      .edit(
+        EditInteractionResponse::new()
+          .content(content)
+          .components(components),
+      )
+      .await;
+  }
+
+  async fn edit(&self, builder: EditInteractionResponse) {
+    if let Err(why) = builder.execute(self.ctx, self.token).await {
+      println!("Cannot edit the setup response: {}", why);
     }
   }
-  if user_id_raw.is_none() {
-    database.close().await;
-    return "Username could not be found, please try again.".to_string();
-  } else {
-    let user_id = user_id_raw.clone().unwrap();
+}
 
-    if sqlx::query!(
-      "SELECT UserID FROM FRONT WHERE UserID=? AND Channel_ID=?",
-      user_id,
-      channel_id,
-    )
-    .fetch_one(&database)
+/// Formats a number with thousands separators, e.g. "12,345".
+fn thousands(number: usize) -> String {
+  let digits = number.to_string();
+  let mut formatted = String::new();
+  for (position, digit) in digits.chars().enumerate() {
+    if position > 0 && (digits.len() - position).is_multiple_of(3) {
+      formatted.push(',');
+    }
+    formatted.push(digit);
+  }
+  formatted
+}
+
+/// Indexes the library of the chosen user and saves the instance.
+///
+/// Returns the instance and a summary of the setup.
+async fn setup(
+  ctx: &Context,
+  guild_id: Option<GuildId>,
+  options: &[CommandDataOption],
+  credentials: &Credentials,
+  user_id: String,
+  progress: &Progress<'_>,
+) -> Result<(Instance, String), String> {
+  let Some(channel_id) = get_channel_option(options, "channel") else {
+    panic!("Discord returned invalid command options.")
+  };
+  if get_instances_by_channel(channel_id)
     .await
-    .is_ok()
-    {
-      database.close().await;
-      return "This UserID has already been added.".to_string();
-    };
+    .iter()
+    .any(|instance| instance.user_id == user_id)
+  {
+    return Err("This UserID has already been added.".to_string());
+  }
+
+  let webhook_url = match setup_webhook(ctx, channel_id, options, credentials).await {
+    Ok(url) => url,
+    Err(why) => return Err(why),
+  };
+
+  let mut add = Instance {
+    active_channel: 1,
+    channel_id,
+    domain: credentials.domain.clone(),
+    token: credentials.token.clone(),
+    user_id,
+    webhook_url,
+    webhook_username: get_string_option(options, "webhook_name"),
+    webhook_avatar: get_string_option(options, "webhook_avatar"),
+    guild_id: guild_id.map(|guild_id| guild_id.get() as i64),
+    ..Default::default()
+  };
+  add.user_name = get_user(&add).await.ok().map(|user| user.Name);
+
+  // New instances announce every library, so everything is indexed in a single query.
+  progress.update("Indexing the libraries…").await;
+  let Ok(items) = get_library_items(&add, None).await else {
+    return Err("Failed to index the libraries of the server.".to_string());
+  };
+  let ids: HashSet<String> = items.into_iter().map(|item| item.Id).collect();
 
-    // If the table already exists in the database then just rename it.
-    if sqlx::query(format!("SELECT {} FROM LIBRARY", &user_id).as_str())
+  // Seen items that have been archived by `/reset` or are used by another instance are picked
+  // up again. Anything else left over from an earlier setup is renamed out of the way.
+  let kept = is_seen_kept(&add.user_id).await;
+  let seen: HashSet<String> = if kept {
+    get_library_by_user(add.user_id.clone())
+      .await
+      .into_iter()
+      .collect()
+  } else {
+    HashSet::new()
+  };
+  let database = get_database().await;
+  if !kept
+    && sqlx::query(format!("SELECT {:?} FROM LIBRARY", &add.user_id).as_str())
       .fetch_one(&database)
       .await
       .is_ok()
-    {
-      sqlx::query(
-        format!(
-          "ALTER TABLE LIBRARY RENAME COLUMN {:?} TO \"{}_{}\"",
-          &user_id,
-          &user_id,
-          chrono::offset::Utc::now().timestamp()
-        )
-        .as_str(),
+  {
+    sqlx::query(
+      format!(
+        "ALTER TABLE LIBRARY RENAME COLUMN {:?} TO \"{}_{}\"",
+        &add.user_id,
+        &add.user_id,
+        chrono::offset::Utc::now().timestamp()
       )
-      .execute(&database)
-      .await
-      .expect("couldn't rename database");
-    };
+      .as_str(),
+    )
+    .execute(&database)
+    .await
+    .expect("couldn't rename database");
+  };
+  sqlx::query(format!("ALTER TABLE LIBRARY ADD {:?} VARCHAR(30)", &add.user_id).as_str())
+    .execute(&database)
+    .await
+    .ok();
+  // The snapshot has to be complete before the instance is saved, otherwise the loop in
+  // `main.rs` would announce the rest of the library.
+  mark_seen(
+    &add.user_id,
+    &ids.difference(&seen).cloned().collect::<Vec<String>>(),
+  )
+  .await;
 
-    // Here, we can only create a new table for the database.
-    // Previously, this segment also requested and inserted the library
-    // from jellyfin into the database, but at least on my setup the
-    // request alone greatly outlives the maximum timeout for discord's
-    // command response, so we just leave it empty and fill it later
-    // within the loop in `main.rs`.
-    sqlx::query(format!("ALTER TABLE LIBRARY ADD {:?} VARCHAR(30)", &user_id).as_str())
-      .execute(&database)
-      .await
-      .ok();
+  let stored_token = crate::crypto::encrypt(&add.token);
+  let stored_webhook_url = add.webhook_url.as_deref().map(crate::crypto::encrypt);
+  add.id = sqlx::query!(
+    "INSERT INTO FRONT (Active_Channel, Channel_ID, Domain, Token, UserID, Webhook_URL, Webhook_Username, Webhook_Avatar, Guild_ID, User_Name) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+    add.active_channel, channel_id, add.domain, stored_token, add.user_id, stored_webhook_url, add.webhook_username, add.webhook_avatar, add.guild_id, add.user_name).execute(&database)
+  .await.expect("insert error").last_insert_rowid();
+  database.close().await;
+  restore_history(add.id, &add.user_id, channel_id).await;
 
-    let add = Instance {
-      active_channel: 1,
-      channel_id: channel_id as i64,
-      domain,
-      token: token.to_string(),
-      user_id,
+  let summary = format!(
+    "Setup successful. Indexed {} items, only new additions will be announced in <#{}>.",
+    thousands(ids.len()),
+    channel_id
+  );
+  Ok((add, summary))
+}
+
+/// Creates the webhook requested through the `webhook` option, or validates the one entered in
+/// the modal.
+///
+/// Webhooks can't be attached to threads, so for those the parent channel receives the webhook
+/// and messages are later executed into the thread.
+async fn setup_webhook(
+  ctx: &Context,
+  channel_id: i64,
+  options: &[CommandDataOption],
+  credentials: &Credentials,
+) -> Result<Option<String>, String> {
+  let create = matches!(
+    get_option(options, "webhook"),
+    Some(CommandDataOptionValue::Boolean(true))
+  );
+  let existing = credentials.webhook_url.clone();
+  if !create && existing.is_none() {
+    if get_string_option(options, "webhook_name").is_some()
+      || get_string_option(options, "webhook_avatar").is_some()
+    {
+      return Err(
+        "A custom name or avatar requires either `webhook` or a webhook URL.".to_string(),
+      );
+    }
+    return Ok(None);
+  }
+
+  let channel_id = ChannelId::new(channel_id as u64);
+  let webhook_channel = match channel_id.to_channel(ctx).await {
+    Ok(Channel::Guild(channel)) => match channel.kind {
+      ChannelType::PublicThread | ChannelType::PrivateThread | ChannelType::NewsThread => {
+        channel.parent_id.unwrap_or(channel_id)
+      },
+      _ => channel_id,
+    },
+    _ => return Err("The selected channel could not be found.".to_string()),
+  };
+
+  if let Some(url) = existing {
+    return match Webhook::from_url(&ctx.http, &url).await {
+      Ok(webhook) if webhook.channel_id == Some(webhook_channel) => Ok(Some(url)),
+      Ok(_) => Err("The webhook you've entered belongs to a different channel.".to_string()),
+      Err(_) => Err("The webhook URL you've entered is invalid.".to_string()),
     };
-    sqlx::query!(
-      "INSERT INTO FRONT (Active_Channel, Channel_ID, Domain, Token, UserID) VALUES (?1, ?2, ?3, ?4, ?5)",
-      add.active_channel, channel_id, add.domain, add.token, add.user_id).execute(&database)
-    .await.expect("insert error");
-    database.close().await;
   }
 
-  "Setup successful.".to_string()
+  let webhook = match webhook_channel
+    .create_webhook(ctx, CreateWebhook::new("JellyCord"))
+    .await
+  {
+    Ok(webhook) => webhook,
+    Err(why) => {
+      return Err(format!(
+        "Failed to create a webhook. Does the bot have the \"Manage Webhooks\" permission?\nError: {why}"
+      ));
+    },
+  };
+  match webhook.url() {
+    Ok(url) => Ok(Some(url)),
+    Err(_) => Err("Discord didn't return a token for the new webhook.".to_string()),
+  }
 }
 
 pub fn register() -> CreateCommand {
   CreateCommand::new("init")
-    .description("Setup notifications for a channel")
+    .description("Initialize current channel and setup jellyfin connection")
     .add_option(
       CreateCommandOption::new(
         CommandOptionType::Channel,
         "channel",
         "Channel to receieve the notifications",
       )
-      .channel_types([ChannelType::Text].to_vec())
-      .required(true),
-    )
-    .add_option(
-      CreateCommandOption::new(
-        CommandOptionType::String,
-        "url",
-        "URL to JellyFin. Without `/web/*`",
-      )
-      .min_length(5)
+      .channel_types(ANNOUNCEMENT_CHANNELS.to_vec())
       .required(true),
     )
+    .add_option(CreateCommandOption::new(
+      CommandOptionType::Boolean,
+      "webhook",
+      "Post through a webhook created by the bot",
+    ))
     .add_option(
       CreateCommandOption::new(
         CommandOptionType::String,
-        "api_key",
-        "API key to access your mediacenter",
-      )
-      .required(true)
-      .min_length(10),
-    )
-    .add_option(
-      CreateCommandOption::new(
-        CommandOptionType::String,
-        "username",
-        "An existing jellyfin/emby user. To limit the scope",
+        "webhook_name",
+        "Username shown on webhook announcements",
       )
-      .required(true),
+      .max_length(80),
     )
+    .add_option(CreateCommandOption::new(
+      CommandOptionType::String,
+      "webhook_avatar",
+      "Image URL used as avatar on webhook announcements",
+    ))
     .default_member_permissions(Permissions::ADMINISTRATOR)
 }
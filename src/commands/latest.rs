@@ -0,0 +1,93 @@
+use serenity::all::{
+  CommandDataOption, CommandOptionType, ComponentInteraction, CreateCommand, CreateCommandOption,
+  CreateInteractionResponse, CreateInteractionResponseMessage, GuildId,
+};
+
+use super::{get_integer_option, get_string_option, item_page, resolve_guild_instance};
+use crate::database::get_instance;
+use crate::{Instance, get_latest_items};
+
+pub async fn run(
+  guild_id: Option<GuildId>,
+  options: &[CommandDataOption],
+) -> CreateInteractionResponseMessage {
+  let server = match resolve_guild_instance(guild_id, options).await {
+    Ok(server) => server,
+    Err(why) => return CreateInteractionResponseMessage::new().content(why),
+  };
+  let kind = get_string_option(options, "type").unwrap_or("all".to_string());
+  let count = get_integer_option(options, "count").unwrap_or(10);
+  page(&server, &kind, count, 0).await
+}
+
+/// Handles the buttons switching between the results of `/latest`.
+pub async fn turn(component: &ComponentInteraction) -> CreateInteractionResponse {
+  let mut parts = component.data.custom_id.split(':').skip(1);
+  let (Some(Ok(id)), Some(kind), Some(Ok(count)), Some(Ok(page_index))) = (
+    parts.next().map(str::parse::<i64>),
+    parts.next(),
+    parts.next().map(str::parse::<i64>),
+    parts.next().map(str::parse::<usize>),
+  ) else {
+    panic!("Discord returned an invalid custom id.")
+  };
+  let message = match get_instance(id).await {
+    Some(server) => page(&server, kind, count, page_index).await,
+    None => CreateInteractionResponseMessage::new()
+      .content("This instance doesn't exist anymore.")
+      .embeds(vec![])
+      .components(vec![]),
+  };
+  CreateInteractionResponse::UpdateMessage(message)
+}
+
+async fn page(
+  server: &Instance,
+  kind: &str,
+  count: i64,
+  page_index: usize,
+) -> CreateInteractionResponseMessage {
+  let types = match kind {
+    "movie" => "Movie",
+    "series" => "Series",
+    "episode" => "Episode",
+    _ => "Movie,Series,Episode",
+  };
+  let Ok(items) = get_latest_items(server, types, count).await else {
+    return CreateInteractionResponseMessage::new()
+      .content("Failed to retrieve the latest items from the server.");
+  };
+  item_page(server, items, page_index, |page_index| {
+    format!("latest:{}:{}:{}:{}", server.id, kind, count, page_index)
+  })
+}
+
+pub fn register() -> CreateCommand {
+  CreateCommand::new("latest")
+    .description("Browse the most recently added items")
+    .add_option(
+      CreateCommandOption::new(
+        CommandOptionType::String,
+        "type",
+        "Only show items of a type",
+      )
+      .add_string_choice("Movies", "movie")
+      .add_string_choice("Series", "series")
+      .add_string_choice("Episodes", "episode"),
+    )
+    .add_option(
+      CreateCommandOption::new(
+        CommandOptionType::Integer,
+        "count",
+        "How many items to show (10 by default)",
+      )
+      .min_int_value(1)
+      .max_int_value(25),
+    )
+    .add_option(CreateCommandOption::new(
+      CommandOptionType::Integer,
+      "instance",
+      "Id of the instance, if the server has several",
+    ))
+    .dm_permission(false)
+}
@@ -0,0 +1,251 @@
+use serenity::all::{
+  CommandDataOption, CommandInteraction, CommandOptionType, ComponentInteraction,
+  ComponentInteractionDataKind, Context, CreateActionRow, CreateCommand, CreateCommandOption,
+  CreateInteractionResponse, CreateInteractionResponseMessage, CreateSelectMenu,
+  CreateSelectMenuKind, CreateSelectMenuOption, EditInteractionResponse,
+};
+
+use super::{Access, get_subcommand, instance_options, is_allowed, resolve_instance};
+use crate::database::{get_instance, get_libraries, get_library_by_user, mark_seen, set_libraries};
+use crate::{Instance, get_library_items, get_views};
+
+/// Indexing newly included libraries can take a while, so the response is deferred.
+pub async fn run(ctx: &Context, command: &CommandInteraction) {
+  if let Err(why) = command
+    .create_response(
+      &ctx.http,
+      CreateInteractionResponse::Defer(CreateInteractionResponseMessage::new().ephemeral(true)),
+    )
+    .await
+  {
+    println!("Cannot respond to slash command: {}", why);
+    return;
+  }
+  let builder = libraries(&command.data.options).await;
+  if let Err(why) = command.edit_response(&ctx.http, builder).await {
+    println!("Cannot edit the libraries response: {}", why);
+  }
+}
+
+async fn libraries(options: &[CommandDataOption]) -> EditInteractionResponse {
+  let (subcommand, options) = get_subcommand(options);
+  let message = EditInteractionResponse::new();
+  let server = match resolve_instance(options).await {
+    Ok(server) => server,
+    Err(why) => return message.content(why),
+  };
+
+  match subcommand {
+    "list" => message.content(list(&server).await),
+    "allow" | "block" => {
+      let mode = if subcommand == "allow" { 1 } else { 2 };
+      match menu(&server, mode).await {
+        Ok(components) => message
+          .content(if mode == 1 {
+            "Select the libraries to announce:"
+          } else {
+            "Select the libraries to exclude from announcements:"
+          })
+          .components(components),
+        Err(why) => message.content(why),
+      }
+    },
+    "all" => match apply(&server, 0, &[]).await {
+      Ok(()) => message.content("Successfully removed the library restriction."),
+      Err(why) => message.content(why),
+    },
+    _ => panic!("Discord returned invalid command options."),
+  }
+}
+
+async fn list(server: &Instance) -> String {
+  let Ok(views) = get_views(server).await else {
+    return "Failed to retrieve the libraries from the server.".to_string();
+  };
+  let libraries = get_libraries(server.id).await;
+  views
+    .iter()
+    .map(|view| {
+      format!(
+        "{} {}",
+        if server.includes_library(&libraries, &view.Id) {
+          ":white_check_mark:"
+        } else {
+          ":x:"
+        },
+        view.Name
+      )
+    })
+    .collect::<Vec<String>>()
+    .join("\n")
+}
+
+/// Builds a select menu of all libraries of an instance for the given mode.
+pub async fn menu(server: &Instance, mode: i64) -> Result<Vec<CreateActionRow>, String> {
+  let Ok(views) = get_views(server).await else {
+    return Err("Failed to retrieve the libraries from the server.".to_string());
+  };
+  let libraries = get_libraries(server.id).await;
+
+  // Discord only allows 25 options per menu.
+  let options: Vec<CreateSelectMenuOption> = views
+    .iter()
+    .take(25)
+    .map(|view| {
+      CreateSelectMenuOption::new(&view.Name, &view.Id)
+        .description(view.CollectionType.clone().unwrap_or("mixed".to_string()))
+        .default_selection(server.library_mode == mode && libraries.contains(&view.Id))
+    })
+    .collect();
+  if options.is_empty() {
+    return Err("The server has no libraries that could be announced.".to_string());
+  }
+  let count = options.len() as u8;
+  let menu = CreateSelectMenu::new(
+    format!("libraries:{}:{}", mode, server.id),
+    CreateSelectMenuKind::String { options },
+  )
+  .min_values(1)
+  .max_values(count);
+
+  Ok(vec![CreateActionRow::SelectMenu(menu)])
+}
+
+/// Saves the libraries picked in the select menu of `menu`.
+pub async fn select(ctx: &Context, component: &ComponentInteraction) {
+  let is_manager = is_allowed(component.member.as_ref(), Access::Manager).await;
+  let response = if is_manager {
+    CreateInteractionResponse::Acknowledge
+  } else {
+    CreateInteractionResponse::Message(
+      CreateInteractionResponseMessage::new()
+        .content("Only managers can change the libraries.")
+        .ephemeral(true),
+    )
+  };
+  if let Err(why) = component.create_response(&ctx.http, response).await {
+    println!("Cannot respond to component: {}", why);
+    return;
+  }
+  if !is_manager {
+    return;
+  }
+
+  let mut parts = component.data.custom_id.split(':').skip(1);
+  let (Some(Ok(mode)), Some(Ok(id))) = (
+    parts.next().map(str::parse::<i64>),
+    parts.next().map(str::parse::<i64>),
+  ) else {
+    panic!("Discord returned an invalid custom id.")
+  };
+  let ComponentInteractionDataKind::StringSelect { values } = &component.data.kind else {
+    panic!("Discord returned an invalid component.")
+  };
+  let content = match get_instance(id).await {
+    None => "This instance doesn't exist anymore.".to_string(),
+    Some(server) => match apply(&server, mode, values).await {
+      Ok(()) if mode == 1 => format!(
+        "Announcements are now limited to {} libraries.",
+        values.len()
+      ),
+      Ok(()) => format!(
+        "{} libraries are now excluded from announcements.",
+        values.len()
+      ),
+      Err(why) => why,
+    },
+  };
+  let builder = EditInteractionResponse::new()
+    .content(content)
+    .components(vec![]);
+  if let Err(why) = component.edit_response(&ctx.http, builder).await {
+    println!("Cannot edit the libraries response: {}", why);
+  }
+}
+
+/// Saves a new library restriction. Items of libraries that weren't announced until now are
+/// marked as seen first, since announcing them would flood the channel. The restriction is
+/// only saved afterwards, so the poller never sees the new libraries unmarked.
+async fn apply(server: &Instance, mode: i64, libraries: &[String]) -> Result<(), String> {
+  let Ok(views) = get_views(server).await else {
+    return Err("Failed to retrieve the libraries from the server.".to_string());
+  };
+  let before = get_libraries(server.id).await;
+  let new = Instance {
+    library_mode: mode,
+    ..server.clone()
+  };
+  let seen = get_library_by_user(server.user_id.clone()).await;
+  // Unrestricted instances fetch the whole server, including items outside of the listed views.
+  if mode == 0 && server.library_mode != 0 {
+    let Ok(items) = get_library_items(server, None).await else {
+      return Err("Failed to retrieve the items of the server.".to_string());
+    };
+    let ids: Vec<String> = items
+      .into_iter()
+      .map(|item| item.Id)
+      .filter(|id| !seen.contains(id))
+      .collect();
+    mark_seen(&server.user_id, &ids).await;
+    set_libraries(server.id, mode, libraries).await;
+    return Ok(());
+  }
+  for view in views {
+    if !new.includes_library(libraries, &view.Id) || server.includes_library(&before, &view.Id) {
+      continue;
+    }
+    let Ok(items) = get_library_items(server, Some(&view.Id)).await else {
+      return Err(format!(
+        "Failed to retrieve the items of \"{}\" from the server.",
+        view.Name
+      ));
+    };
+    let ids: Vec<String> = items
+      .into_iter()
+      .map(|item| item.Id)
+      .filter(|id| !seen.contains(id))
+      .collect();
+    mark_seen(&server.user_id, &ids).await;
+  }
+  set_libraries(server.id, mode, libraries).await;
+  Ok(())
+}
+
+pub fn register() -> CreateCommand {
+  let [channel, instance] = instance_options();
+  CreateCommand::new("libraries")
+    .description("Restrict a channel to some of the server's libraries")
+    .add_option(
+      CreateCommandOption::new(CommandOptionType::SubCommand, "list", "List all libraries")
+        .add_sub_option(channel.clone())
+        .add_sub_option(instance.clone()),
+    )
+    .add_option(
+      CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "allow",
+        "Only announce items of the selected libraries",
+      )
+      .add_sub_option(channel.clone())
+      .add_sub_option(instance.clone()),
+    )
+    .add_option(
+      CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "block",
+        "Announce items of all but the selected libraries",
+      )
+      .add_sub_option(channel.clone())
+      .add_sub_option(instance.clone()),
+    )
+    .add_option(
+      CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "all",
+        "Announce items of all libraries",
+      )
+      .add_sub_option(channel)
+      .add_sub_option(instance),
+    )
+    .dm_permission(false)
+}
@@ -0,0 +1,99 @@
+use serenity::all::{
+  ButtonStyle, CommandDataOption, ComponentInteraction, CreateActionRow, CreateButton,
+  CreateCommand, CreateEmbed, CreateEmbedFooter, CreateInteractionResponse,
+  CreateInteractionResponseMessage, GuildId,
+};
+
+use crate::database::get_instances_by_guild;
+
+const PAGE_SIZE: usize = 10;
+
+pub async fn run(
+  guild_id: Option<GuildId>,
+  _options: &[CommandDataOption],
+) -> CreateInteractionResponseMessage {
+  let message = CreateInteractionResponseMessage::new();
+  let Some(guild_id) = guild_id else {
+    return message.content("Instances can only be listed within a server.");
+  };
+  let (embed, components) = page(guild_id, 0).await;
+  message.embed(embed).components(components)
+}
+
+/// Handles the buttons switching between the pages of `/list`.
+pub async fn turn(component: &ComponentInteraction) -> CreateInteractionResponse {
+  let Some(Ok(page_index)) = component
+    .data
+    .custom_id
+    .split(':')
+    .nth(1)
+    .map(str::parse::<usize>)
+  else {
+    panic!("Discord returned an invalid custom id.")
+  };
+  let Some(guild_id) = component.guild_id else {
+    return CreateInteractionResponse::Acknowledge;
+  };
+  let (embed, components) = page(guild_id, page_index).await;
+  CreateInteractionResponse::UpdateMessage(
+    CreateInteractionResponseMessage::new()
+      .embed(embed)
+      .components(components),
+  )
+}
+
+async fn page(guild_id: GuildId, page_index: usize) -> (CreateEmbed, Vec<CreateActionRow>) {
+  let instances = get_instances_by_guild(guild_id.get() as i64).await;
+  let pages = instances.len().div_ceil(PAGE_SIZE).max(1);
+  let page_index = page_index.min(pages - 1);
+
+  let mut embed = CreateEmbed::new()
+    .title("Instances")
+    .footer(CreateEmbedFooter::new(format!(
+      "Page {} of {} - {} instances",
+      page_index + 1,
+      pages,
+      instances.len()
+    )));
+  if instances.is_empty() {
+    embed = embed.description("There are no instances in this server. Use `/init` to add one.");
+  }
+  for server in instances
+    .iter()
+    .skip(page_index * PAGE_SIZE)
+    .take(PAGE_SIZE)
+  {
+    let user = server.user_name.clone().unwrap_or(server.user_id.clone());
+    let state = if server.active_channel == 1 {
+      "Active"
+    } else {
+      "Paused"
+    };
+    embed = embed.field(
+      format!("{} - {}", server.id, server.domain),
+      format!("<#{}> - {} - {}", server.channel_id, user, state),
+      false,
+    );
+  }
+
+  if pages == 1 {
+    return (embed, vec![]);
+  }
+  let buttons = vec![
+    CreateButton::new(format!("list:{}", page_index.saturating_sub(1)))
+      .label("Previous")
+      .style(ButtonStyle::Secondary)
+      .disabled(page_index == 0),
+    CreateButton::new(format!("list:{}", page_index + 1))
+      .label("Next")
+      .style(ButtonStyle::Secondary)
+      .disabled(page_index + 1 >= pages),
+  ];
+  (embed, vec![CreateActionRow::Buttons(buttons)])
+}
+
+pub fn register() -> CreateCommand {
+  CreateCommand::new("list")
+    .description("List every instance in this server")
+    .dm_permission(false)
+}
@@ -0,0 +1,61 @@
+use serenity::all::{
+  CommandDataOption, CommandDataOptionValue, CommandOptionType, CreateCommand, CreateCommandOption,
+  GuildId, Permissions,
+};
+
+use super::{get_option, get_subcommand};
+use crate::database::{get_manager_role, set_manager_role};
+
+pub async fn run(guild_id: Option<GuildId>, options: &[CommandDataOption]) -> String {
+  let Some(guild_id) = guild_id else {
+    return "Managers can only be set within a server.".to_string();
+  };
+  let guild_id = guild_id.get() as i64;
+  let (subcommand, options) = get_subcommand(options);
+  match subcommand {
+    "set" => {
+      let Some(CommandDataOptionValue::Role(role_id)) = get_option(options, "role") else {
+        panic!("Discord returned invalid command options.")
+      };
+      set_manager_role(guild_id, Some(role_id.get() as i64)).await;
+      format!("Members with <@&{role_id}> can now manage announcements.")
+    },
+    "clear" => {
+      set_manager_role(guild_id, None).await;
+      "Only administrators can manage announcements now.".to_string()
+    },
+    "show" => match get_manager_role(guild_id).await {
+      Some(role_id) => format!("Members with <@&{role_id}> can manage announcements."),
+      None => "There is no manager role, only administrators can manage announcements.".to_string(),
+    },
+    _ => panic!("Discord returned invalid command options."),
+  }
+}
+
+pub fn register() -> CreateCommand {
+  CreateCommand::new("managers")
+    .description("Choose the role that may use the manager commands")
+    .add_option(
+      CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "set",
+        "Let members with a role manage announcements",
+      )
+      .add_sub_option(
+        CreateCommandOption::new(CommandOptionType::Role, "role", "The manager role")
+          .required(true),
+      ),
+    )
+    .add_option(CreateCommandOption::new(
+      CommandOptionType::SubCommand,
+      "clear",
+      "Only let administrators manage announcements",
+    ))
+    .add_option(CreateCommandOption::new(
+      CommandOptionType::SubCommand,
+      "show",
+      "Show the manager role",
+    ))
+    .default_member_permissions(Permissions::ADMINISTRATOR)
+    .dm_permission(false)
+}
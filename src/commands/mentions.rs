@@ -0,0 +1,257 @@
+use serenity::all::{
+  CommandDataOption, CommandDataOptionValue, CommandOptionType, Context, CreateCommand,
+  CreateCommandOption, EditRole, GuildId,
+};
+
+use super::{
+  get_integer_option, get_option, get_string_option, get_subcommand, instance_options,
+  resolve_instance,
+};
+use crate::database::{get_all_followers, get_database, get_mentions};
+use crate::follows::sync_roles;
+use crate::{Instance, get_views, search_items};
+
+pub async fn run(
+  ctx: &Context,
+  guild_id: Option<GuildId>,
+  options: &[CommandDataOption],
+) -> String {
+  let (subcommand, options) = get_subcommand(options);
+  let server = match resolve_instance(options).await {
+    Ok(server) => server,
+    Err(why) => return why,
+  };
+
+  match subcommand {
+    "add" => add(&server, options).await,
+    "series" => series(ctx, guild_id, &server, options).await,
+    "list" => list(&server).await,
+    "remove" => remove(&server, options).await,
+    _ => panic!("Discord returned invalid command options."),
+  }
+}
+
+fn get_role_option(options: &[CommandDataOption]) -> Option<i64> {
+  match get_option(options, "role") {
+    Some(CommandDataOptionValue::Role(role)) => Some(role.get() as i64),
+    _ => None,
+  }
+}
+
+async fn insert(server: &Instance, kind: &str, value: &str, role_id: i64) {
+  let database = get_database().await;
+  sqlx::query!(
+    "INSERT INTO MENTIONS (Instance_ID, Kind, Value, Role_ID) VALUES (?1, ?2, ?3, ?4)",
+    server.id,
+    kind,
+    value,
+    role_id
+  )
+  .execute(&database)
+  .await
+  .expect("insert error");
+  database.close().await;
+}
+
+async fn add(server: &Instance, options: &[CommandDataOption]) -> String {
+  let (Some(kind), Some(role_id)) = (get_string_option(options, "kind"), get_role_option(options))
+  else {
+    panic!("Discord returned invalid command options.")
+  };
+  let value = get_string_option(options, "value")
+    .unwrap_or_default()
+    .trim()
+    .to_string();
+
+  let value = match kind.as_str() {
+    "all" => String::new(),
+    _ if value.is_empty() => return "Please enter a genre or library as `value`.".to_string(),
+    "library" => {
+      let Ok(views) = get_views(server).await else {
+        return "Failed to retrieve the libraries from the server.".to_string();
+      };
+      match views
+        .iter()
+        .find(|view| view.Name.eq_ignore_ascii_case(&value) || view.Id == value)
+      {
+        Some(view) => view.Id.clone(),
+        None => return "Library could not be found.".to_string(),
+      }
+    },
+    _ => value,
+  };
+
+  insert(server, &kind, &value, role_id).await;
+  format!("<@&{role_id}> will now be mentioned.")
+}
+
+/// Creates a role for the followers of a series, unless an existing one was given.
+async fn series(
+  ctx: &Context,
+  guild_id: Option<GuildId>,
+  server: &Instance,
+  options: &[CommandDataOption],
+) -> String {
+  let Some(name) = get_string_option(options, "series") else {
+    panic!("Discord returned invalid command options.")
+  };
+  let Ok(results) = search_items(server, &name, "Series").await else {
+    return "Failed to search the server.".to_string();
+  };
+  let Some(series) = results
+    .iter()
+    .find(|item| item.Name.eq_ignore_ascii_case(name.trim()))
+    .or(results.first())
+  else {
+    return "Series could not be found.".to_string();
+  };
+
+  let role_id = match (get_role_option(options), guild_id) {
+    (Some(role_id), _) => role_id,
+    (None, Some(guild_id)) => {
+      let role_name: String = format!("{} followers", series.Name)
+        .chars()
+        .take(100)
+        .collect();
+      match guild_id
+        .create_role(ctx, EditRole::new().name(role_name).mentionable(true))
+        .await
+      {
+        Ok(role) => role.id.get() as i64,
+        Err(why) => {
+          return format!(
+            "Failed to create a role. Does the bot have the \"Manage Roles\" permission?\nError: {why}"
+          );
+        },
+      }
+    },
+    (None, None) => return "Roles can only be created within a server.".to_string(),
+  };
+
+  insert(server, "series", &series.Id, role_id).await;
+  // Members following the series already get the role, later followers receive it through
+  // `/follow`.
+  let followers = get_all_followers(server.id, &series.Id).await;
+  let mut failed = 0;
+  for user_id in &followers {
+    failed += sync_roles(ctx, server, &series.Id, *user_id, true)
+      .await
+      .len();
+  }
+  let mut response = format!(
+    "<@&{role_id}> will now be mentioned for new episodes of \"{}\". Members get it with `/follow`.",
+    series.Name
+  );
+  if failed > 0 {
+    response.push_str(&format!(
+      "\nFailed to give the role to {failed} of {} followers. Is it below the bot's highest role?",
+      followers.len()
+    ));
+  }
+  response
+}
+
+async fn list(server: &Instance) -> String {
+  let mentions = get_mentions(server.id).await;
+  if mentions.is_empty() {
+    return "No one is mentioned.".to_string();
+  }
+
+  mentions
+    .iter()
+    .map(|mention| {
+      let description = match mention.kind.as_str() {
+        "all" => "every announcement".to_string(),
+        kind => format!("{} \"{}\"", kind, mention.value),
+      };
+      format!(
+        "`{}` <@&{}> for {}",
+        mention.id, mention.role_id, description
+      )
+    })
+    .collect::<Vec<String>>()
+    .join("\n")
+}
+
+async fn remove(server: &Instance, options: &[CommandDataOption]) -> String {
+  let Some(id) = get_integer_option(options, "id") else {
+    panic!("Discord returned invalid command options.")
+  };
+
+  let database = get_database().await;
+  let result = sqlx::query!(
+    "DELETE FROM MENTIONS WHERE ID=? AND Instance_ID=?",
+    id,
+    server.id
+  )
+  .execute(&database)
+  .await
+  .expect("delete error");
+  database.close().await;
+
+  if result.rows_affected() == 0 {
+    "Mention could not be found.".to_string()
+  } else {
+    "Successfully removed mention.".to_string()
+  }
+}
+
+pub fn register() -> CreateCommand {
+  let [channel, instance] = instance_options();
+  CreateCommand::new("mentions")
+    .description("Mention roles for new announcements")
+    .add_option(
+      CreateCommandOption::new(CommandOptionType::SubCommand, "add", "Mention a role")
+        .add_sub_option(channel.clone())
+        .add_sub_option(
+          CreateCommandOption::new(CommandOptionType::Role, "role", "Role to mention")
+            .required(true),
+        )
+        .add_sub_option(
+          CreateCommandOption::new(CommandOptionType::String, "kind", "When to mention it")
+            .add_string_choice("Every announcement", "all")
+            .add_string_choice("Genre", "genre")
+            .add_string_choice("Library", "library")
+            .required(true),
+        )
+        .add_sub_option(CreateCommandOption::new(
+          CommandOptionType::String,
+          "value",
+          "Genre or library name",
+        ))
+        .add_sub_option(instance.clone()),
+    )
+    .add_option(
+      CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "series",
+        "Mention a follower role for new episodes of a series",
+      )
+      .add_sub_option(channel.clone())
+      .add_sub_option(
+        CreateCommandOption::new(CommandOptionType::String, "series", "Name of the series")
+          .required(true),
+      )
+      .add_sub_option(CreateCommandOption::new(
+        CommandOptionType::Role,
+        "role",
+        "Existing role to use. A new one is created otherwise",
+      ))
+      .add_sub_option(instance.clone()),
+    )
+    .add_option(
+      CreateCommandOption::new(CommandOptionType::SubCommand, "list", "List all mentions")
+        .add_sub_option(channel.clone())
+        .add_sub_option(instance.clone()),
+    )
+    .add_option(
+      CreateCommandOption::new(CommandOptionType::SubCommand, "remove", "Remove a mention")
+        .add_sub_option(channel)
+        .add_sub_option(
+          CreateCommandOption::new(CommandOptionType::Integer, "id", "Id of the mention")
+            .required(true),
+        )
+        .add_sub_option(instance),
+    )
+    .dm_permission(false)
+}
@@ -1,5 +1,550 @@
+use serde_json::{Value, json};
+use serenity::all::{
+  ButtonStyle, ChannelType, Command, CommandDataOption, CommandDataOptionValue, CommandOptionType,
+  Context, CreateActionRow, CreateAutocompleteResponse, CreateButton, CreateCommand,
+  CreateCommandOption, CreateEmbedFooter, CreateInteractionResponseMessage, GuildId, Member,
+};
+
+use crate::database::{get_instances_by_channel, get_instances_by_guild, get_manager_role};
+use crate::{Instance, Item, announcement, get_item, search_items};
+
+pub mod digest;
+pub mod edit;
+pub mod filter;
+pub mod follow;
+pub mod following;
 pub mod help;
+pub mod history;
 pub mod init;
+pub mod latest;
+pub mod libraries;
+pub mod list;
+pub mod managers;
+pub mod mentions;
 pub mod pause;
 pub mod ping;
+pub mod popular;
+pub mod preview;
+pub mod quiet;
 pub mod reset;
+pub mod resume;
+pub mod resync;
+pub mod route;
+pub mod search;
+pub mod stats;
+pub mod status;
+pub mod unfollow;
+
+/// Channel types announcements can be posted to.
+pub const ANNOUNCEMENT_CHANNELS: [ChannelType; 4] = [
+  ChannelType::Text,
+  ChannelType::News,
+  ChannelType::PublicThread,
+  ChannelType::Forum,
+];
+
+/// Who may use a command. Administrators may use every command.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Access {
+  /// Everyone, also in direct messages.
+  Member,
+  /// Administrators and members with the manager role of the guild, see `/managers`.
+  Manager,
+  /// Administrators only, for commands that handle API keys or delete instances.
+  Admin,
+}
+
+/// A command of the bot, as registered with discord and listed by `/help` with its registered
+/// description.
+pub struct CommandInfo {
+  pub name: &'static str,
+  pub access: Access,
+  pub register: fn() -> CreateCommand,
+}
+
+/// Every command of the bot. `/help` lists them in this order, grouped by access.
+pub const CATALOG: &[CommandInfo] = &[
+  CommandInfo {
+    name: "init",
+    access: Access::Admin,
+    register: init::register,
+  },
+  CommandInfo {
+    name: "reset",
+    access: Access::Admin,
+    register: reset::register,
+  },
+  CommandInfo {
+    name: "edit",
+    access: Access::Admin,
+    register: edit::register,
+  },
+  CommandInfo {
+    name: "managers",
+    access: Access::Admin,
+    register: managers::register,
+  },
+  CommandInfo {
+    name: "list",
+    access: Access::Manager,
+    register: list::register,
+  },
+  CommandInfo {
+    name: "pause",
+    access: Access::Manager,
+    register: pause::register,
+  },
+  CommandInfo {
+    name: "resume",
+    access: Access::Manager,
+    register: resume::register,
+  },
+  CommandInfo {
+    name: "resync",
+    access: Access::Manager,
+    register: resync::register,
+  },
+  CommandInfo {
+    name: "libraries",
+    access: Access::Manager,
+    register: libraries::register,
+  },
+  CommandInfo {
+    name: "route",
+    access: Access::Manager,
+    register: route::register,
+  },
+  CommandInfo {
+    name: "filter",
+    access: Access::Manager,
+    register: filter::register,
+  },
+  CommandInfo {
+    name: "digest",
+    access: Access::Manager,
+    register: digest::register,
+  },
+  CommandInfo {
+    name: "quiet",
+    access: Access::Manager,
+    register: quiet::register,
+  },
+  CommandInfo {
+    name: "mentions",
+    access: Access::Manager,
+    register: mentions::register,
+  },
+  CommandInfo {
+    name: "preview",
+    access: Access::Manager,
+    register: preview::register,
+  },
+  CommandInfo {
+    name: "history",
+    access: Access::Manager,
+    register: history::register,
+  },
+  CommandInfo {
+    name: "stats",
+    access: Access::Manager,
+    register: stats::register,
+  },
+  CommandInfo {
+    name: "status",
+    access: Access::Manager,
+    register: status::register,
+  },
+  CommandInfo {
+    name: "help",
+    access: Access::Member,
+    register: help::register,
+  },
+  CommandInfo {
+    name: "ping",
+    access: Access::Member,
+    register: ping::register,
+  },
+  CommandInfo {
+    name: "follow",
+    access: Access::Member,
+    register: follow::register,
+  },
+  CommandInfo {
+    name: "following",
+    access: Access::Member,
+    register: following::register,
+  },
+  CommandInfo {
+    name: "unfollow",
+    access: Access::Member,
+    register: unfollow::register,
+  },
+  CommandInfo {
+    name: "popular",
+    access: Access::Member,
+    register: popular::register,
+  },
+  CommandInfo {
+    name: "latest",
+    access: Access::Member,
+    register: latest::register,
+  },
+  CommandInfo {
+    name: "search",
+    access: Access::Member,
+    register: search::register,
+  },
+];
+
+/// Returns who may use a command. This is the only place access is decided, `interaction_create`
+/// checks it before dispatching. Commands missing from the catalog are limited to administrators.
+pub fn access(name: &str) -> Access {
+  match CATALOG.iter().find(|command| command.name == name) {
+    Some(command) => command.access,
+    None => {
+      eprintln!("The command \"{name}\" is missing from the catalog");
+      Access::Admin
+    },
+  }
+}
+
+/// Registers the commands of the catalog, either globally or only in `dev_guild`, where changes
+/// show up instantly. Nothing is sent if discord already knows the same commands, otherwise
+/// all of them are overwritten at once, which also removes commands that don't exist anymore.
+pub async fn register_commands(
+  ctx: &Context,
+  dev_guild: Option<GuildId>,
+) -> Result<(), serenity::Error> {
+  let commands: Vec<CreateCommand> = CATALOG.iter().map(|command| (command.register)()).collect();
+  let registered = match dev_guild {
+    Some(guild_id) => guild_id.get_commands(&ctx.http).await?,
+    None => Command::get_global_commands(&ctx.http).await?,
+  };
+
+  let in_guild = dev_guild.is_some();
+  let mut wanted: Vec<Value> = commands
+    .iter()
+    .map(|command| comparable(serde_json::to_value(command), in_guild))
+    .collect();
+  let mut current: Vec<Value> = registered
+    .iter()
+    .map(|command| comparable(serde_json::to_value(command), in_guild))
+    .collect();
+  let by_name = |x: &Value, y: &Value| x["name"].as_str().cmp(&y["name"].as_str());
+  wanted.sort_by(by_name);
+  current.sort_by(by_name);
+  if wanted == current {
+    println!("Commands are up to date.");
+    return Ok(());
+  }
+
+  let count = commands.len();
+  match dev_guild {
+    Some(guild_id) => {
+      guild_id.set_commands(&ctx.http, commands).await?;
+    },
+    None => {
+      Command::set_global_commands(&ctx.http, commands).await?;
+    },
+  }
+  println!(
+    "Registered {count} commands, {} were registered before.",
+    registered.len()
+  );
+  Ok(())
+}
+
+/// Reduces a command to the parts that are sent when registering it. Discord fills in defaults
+/// for the rest, so registered commands never equal the builders completely.
+fn comparable(command: serde_json::Result<Value>, in_guild: bool) -> Value {
+  let command = command.unwrap_or_default();
+  let options = match &command["options"] {
+    Value::Null => json!([]),
+    options => options.clone(),
+  };
+  // Guild commands don't exist in direct messages anyway.
+  let dm_permission = match &command["dm_permission"] {
+    _ if in_guild => Value::Null,
+    Value::Null => json!(true),
+    dm_permission => dm_permission.clone(),
+  };
+  json!({
+    "name": command["name"],
+    "description": command["description"],
+    "options": options,
+    "default_member_permissions": command["default_member_permissions"],
+    "dm_permission": dm_permission,
+  })
+}
+
+/// Whether a member may use something of the given access level. `member` is `None` in direct
+/// messages.
+pub async fn is_allowed(member: Option<&Member>, access: Access) -> bool {
+  if access == Access::Member {
+    return true;
+  }
+  let Some(member) = member else {
+    return false;
+  };
+  if member
+    .permissions
+    .is_some_and(|permissions| permissions.administrator())
+  {
+    return true;
+  }
+  access == Access::Manager
+    && get_manager_role(member.guild_id.get() as i64)
+      .await
+      .is_some_and(|role_id| member.roles.iter().any(|role| role.get() as i64 == role_id))
+}
+
+/// Looks up a command option by name, since optional options can arrive in any order.
+pub fn get_option<'a>(
+  options: &'a [CommandDataOption],
+  name: &str,
+) -> Option<&'a CommandDataOptionValue> {
+  options
+    .iter()
+    .find(|option| option.name == name)
+    .map(|option| &option.value)
+}
+
+pub fn get_string_option(options: &[CommandDataOption], name: &str) -> Option<String> {
+  match get_option(options, name) {
+    Some(CommandDataOptionValue::String(text)) => Some(text.clone()),
+    _ => None,
+  }
+}
+
+pub fn get_channel_option(options: &[CommandDataOption], name: &str) -> Option<i64> {
+  match get_option(options, name) {
+    Some(CommandDataOptionValue::Channel(channel)) => Some(channel.get() as i64),
+    _ => None,
+  }
+}
+
+pub fn get_integer_option(options: &[CommandDataOption], name: &str) -> Option<i64> {
+  match get_option(options, name) {
+    Some(CommandDataOptionValue::Integer(integer)) => Some(*integer),
+    _ => None,
+  }
+}
+
+/// Returns the options of the subcommand that was used.
+pub fn get_subcommand(options: &[CommandDataOption]) -> (&str, &[CommandDataOption]) {
+  match options.first() {
+    Some(CommandDataOption {
+      name,
+      value: CommandDataOptionValue::SubCommand(options),
+      ..
+    }) => (name.as_str(), options.as_slice()),
+    _ => panic!("Discord returned invalid command options."),
+  }
+}
+
+/// The `channel` and `instance` options used to pick an instance.
+pub fn instance_options() -> [CreateCommandOption; 2] {
+  [
+    CreateCommandOption::new(
+      CommandOptionType::Channel,
+      "channel",
+      "Channel of the instance",
+    )
+    .channel_types(ANNOUNCEMENT_CHANNELS.to_vec())
+    .required(true),
+    CreateCommandOption::new(
+      CommandOptionType::Integer,
+      "instance",
+      "Id of the instance, if the channel has several",
+    ),
+  ]
+}
+
+/// Finds the instance selected through the `channel` and `instance` options.
+pub async fn resolve_instance(options: &[CommandDataOption]) -> Result<Instance, String> {
+  let Some(channel_id) = get_channel_option(options, "channel") else {
+    panic!("Discord returned invalid command options.")
+  };
+  let mut instances = get_instances_by_channel(channel_id).await;
+  if let Some(id) = get_integer_option(options, "instance") {
+    instances.retain(|instance| instance.id == id);
+  }
+  match instances.len() {
+    0 => Err("There is no instance for this channel.".to_string()),
+    1 => Ok(instances.remove(0)),
+    _ => Err(format!(
+      "This channel has several instances, please specify one: {}",
+      instances
+        .iter()
+        .map(|instance| format!("`{}` ({})", instance.id, instance.domain))
+        .collect::<Vec<String>>()
+        .join(", ")
+    )),
+  }
+}
+
+/// Finds the instances selected through the `channel` and `instance` options. Without
+/// `instance`, every instance of the channel is selected.
+pub async fn resolve_instances(options: &[CommandDataOption]) -> Result<Vec<Instance>, String> {
+  let Some(channel_id) = get_channel_option(options, "channel") else {
+    panic!("Discord returned invalid command options.")
+  };
+  let mut instances = get_instances_by_channel(channel_id).await;
+  if let Some(id) = get_integer_option(options, "instance") {
+    instances.retain(|instance| instance.id == id);
+  }
+  if instances.is_empty() {
+    return Err("There is no instance for this channel.".to_string());
+  }
+  Ok(instances)
+}
+
+/// Finds the instance used by member commands: the one given through the `instance` option, or
+/// the first one of the guild.
+pub async fn resolve_guild_instance(
+  guild_id: Option<GuildId>,
+  options: &[CommandDataOption],
+) -> Result<Instance, String> {
+  let Some(guild_id) = guild_id else {
+    return Err("The library can only be browsed within a server.".to_string());
+  };
+  let mut instances = get_instances_by_guild(guild_id.get() as i64).await;
+  if let Some(id) = get_integer_option(options, "instance") {
+    instances.retain(|instance| instance.id == id);
+  }
+  if instances.is_empty() {
+    return Err("This server doesn't announce any media yet.".to_string());
+  }
+  Ok(instances.remove(0))
+}
+
+/// Shows one of several items the way it would be announced, with buttons to switch between them.
+///
+/// `custom_id` returns the custom id of the button leading to a page.
+pub fn item_page(
+  server: &Instance,
+  mut items: Vec<Item>,
+  page_index: usize,
+  custom_id: impl Fn(usize) -> String,
+) -> CreateInteractionResponseMessage {
+  let message = CreateInteractionResponseMessage::new();
+  if items.is_empty() {
+    return message
+      .content("Nothing has been found.")
+      .embeds(vec![])
+      .components(vec![]);
+  }
+  let pages = items.len();
+  let page_index = page_index.min(pages - 1);
+  let mut embeds = announcement::render_item(server, items.swap_remove(page_index)).embeds();
+  if let Some(last) = embeds.pop() {
+    embeds.push(last.footer(CreateEmbedFooter::new(format!(
+      "{} of {}",
+      page_index + 1,
+      pages
+    ))));
+  }
+  if pages == 1 {
+    return message.embeds(embeds).components(vec![]);
+  }
+  let buttons = vec![
+    CreateButton::new(custom_id(page_index.saturating_sub(1)))
+      .label("Previous")
+      .style(ButtonStyle::Secondary)
+      .disabled(page_index == 0),
+    CreateButton::new(custom_id(page_index + 1))
+      .label("Next")
+      .style(ButtonStyle::Secondary)
+      .disabled(page_index + 1 >= pages),
+  ];
+  message
+    .embeds(embeds)
+    .components(vec![CreateActionRow::Buttons(buttons)])
+}
+
+/// Resolves an item option, which is either a choice of [`item_choices`] or a name.
+pub async fn find_item<'a>(
+  instances: &'a [Instance],
+  value: &str,
+  types: &str,
+) -> Option<(&'a Instance, Item)> {
+  if let Some((instance_id, item_id)) = value.split_once(':')
+    && let Some(server) = instances
+      .iter()
+      .find(|instance| instance.id.to_string() == instance_id)
+    && let Ok(item) = get_item(server, item_id).await
+  {
+    return Some((server, item));
+  }
+
+  let name = value.trim();
+  for server in instances {
+    let Ok(results) = search_items(server, name, types).await else {
+      continue;
+    };
+    if let Some(item) = results
+      .iter()
+      .find(|item| item.Name.eq_ignore_ascii_case(name))
+      .or(results.first())
+    {
+      return Some((server, item.clone()));
+    }
+  }
+  None
+}
+
+/// Suggests items of the server's instances matching what has been typed so far.
+pub async fn item_choices(
+  guild_id: Option<GuildId>,
+  value: &str,
+  types: &str,
+) -> CreateAutocompleteResponse {
+  let mut response = CreateAutocompleteResponse::new();
+  let (Some(guild_id), false) = (guild_id, value.trim().is_empty()) else {
+    return response;
+  };
+
+  let instances = get_instances_by_guild(guild_id.get() as i64).await;
+  let mut count = 0;
+  for server in &instances {
+    let Ok(results) = search_items(server, value.trim(), types).await else {
+      continue;
+    };
+    for item in results {
+      if count == 25 {
+        return response;
+      }
+      let mut name = item.to_string();
+      if instances.len() > 1 {
+        name = format!("{name} - {}", server.domain);
+      }
+      response = response.add_string_choice(
+        name.chars().take(100).collect::<String>(),
+        format!("{}:{}", server.id, item.Id),
+      );
+      count += 1;
+    }
+  }
+  response
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Access, CATALOG, access};
+
+  /// Discord reports the name a command has been registered with, which has to be the one
+  /// its access is looked up by.
+  #[test]
+  fn catalog_names_match_registrations() {
+    for command in CATALOG {
+      let registered = serde_json::to_value((command.register)()).unwrap();
+      assert_eq!(registered["name"], command.name);
+      assert_eq!(access(command.name), command.access);
+    }
+  }
+
+  #[test]
+  fn unknown_commands_need_an_administrator() {
+    assert_eq!(access("unknown"), Access::Admin);
+  }
+}
@@ -1,79 +1,118 @@
-use serenity::all::{
-  ChannelType, CommandDataOption, CommandDataOptionValue, CommandOptionType, CreateCommand,
-  CreateCommandOption, Permissions,
-};
+use serenity::all::{CommandDataOption, CommandOptionType, CreateCommand, CreateCommandOption};
 
-pub async fn run(options: &[CommandDataOption]) -> String {
-  let database = sqlx::sqlite::SqlitePoolOptions::new()
-    .max_connections(5)
-    .connect_with(
-      sqlx::sqlite::SqliteConnectOptions::new()
-        .filename("jellycord.sqlite")
-        .create_if_missing(true),
-    )
-    .await
-    .expect("Couldn't connect to database");
+use super::{get_string_option, instance_options, resolve_instances};
+use crate::database::pause_instance;
 
-  let channel_id = match options.get(0).unwrap().value {
-    CommandDataOptionValue::Channel(integer) => integer.get() as i64,
-    _ => {
-      panic!("Discord returned invalid command options.")
+pub async fn run(options: &[CommandDataOption]) -> String {
+  let instances = match resolve_instances(options).await {
+    Ok(instances) => instances,
+    Err(why) => return why,
+  };
+  let until = match get_string_option(options, "duration") {
+    Some(duration) => match parse_duration(&duration)
+      .and_then(|seconds| chrono::offset::Utc::now().timestamp().checked_add(seconds))
+    {
+      Some(until) => Some(until),
+      None => return "The duration has to look like \"6h\", \"30m\" or \"1d12h\".".to_string(),
     },
+    None => None,
   };
+  let reason = get_string_option(options, "reason");
+  let queue = get_string_option(options, "mode").as_deref() == Some("queue");
 
-  sqlx::query!(
-    "UPDATE FRONT SET Active_Channel = 0 WHERE Channel_ID=?",
-    channel_id
-  )
-  .execute(&database)
-  .await
-  .expect("pause error");
+  for server in &instances {
+    pause_instance(server.id, until, reason.as_deref(), queue).await;
+  }
 
-  match sqlx::query!(
-    "SELECT Active_Channel FROM FRONT WHERE Channel_ID=?",
-    channel_id
-  )
-  .fetch_one(&database)
-  .await
-  {
-    Ok(fd) => {
-      if fd.Active_Channel == 1 {
-        sqlx::query!(
-          "UPDATE FRONT SET Active_Channel = 0 WHERE Channel_ID=?",
-          channel_id
-        )
-        .execute(&database)
-        .await
-        .expect("pause error");
-        database.close().await;
-        "Successfully paused channel.".to_string()
-      } else {
-        sqlx::query!(
-          "UPDATE FRONT SET Active_Channel = 1 WHERE Channel_ID=?",
-          channel_id
-        )
-        .execute(&database)
-        .await
-        .expect("unpause error");
-        database.close().await;
-        "Successfully unpaused channel.".to_string()
-      }
-    },
-    _ => "Internal error".to_string(),
+  let mut response = if instances.len() == 1 {
+    "Successfully paused the instance".to_string()
+  } else {
+    format!("Successfully paused {} instances", instances.len())
+  };
+  match until {
+    Some(until) => response.push_str(&format!(" until <t:{until}:f>.")),
+    None => response.push_str(". Use `/resume` to continue."),
   }
+  response.push_str(if queue {
+    "\nNew items are queued and posted once it resumes."
+  } else {
+    "\nNew items are marked as seen and won't be announced."
+  });
+  response
+}
+
+/// Parses durations like "6h", "30m" or "1d12h" into seconds. Durations that don't fit are
+/// rejected.
+fn parse_duration(duration: &str) -> Option<i64> {
+  let mut seconds: i64 = 0;
+  let mut number = String::new();
+  for character in duration.trim().to_lowercase().chars() {
+    if character.is_ascii_digit() {
+      number.push(character);
+      continue;
+    }
+    let unit = match character {
+      'w' => 604800,
+      'd' => 86400,
+      'h' => 3600,
+      'm' => 60,
+      _ => return None,
+    };
+    seconds = seconds.checked_add(number.parse::<i64>().ok()?.checked_mul(unit)?)?;
+    number.clear();
+  }
+  if !number.is_empty() || seconds == 0 {
+    return None;
+  }
+  Some(seconds)
 }
 
 pub fn register() -> CreateCommand {
+  let [channel, instance] = instance_options();
   CreateCommand::new("pause")
-    .description("Un/Pause notifications for a channel")
+    .description("Pause announcements, optionally for a while and queueing new items")
+    .add_option(channel)
+    .add_option(CreateCommandOption::new(
+      CommandOptionType::String,
+      "duration",
+      "Resume on its own after e.g. \"6h\" or \"1d12h\"",
+    ))
+    .add_option(
+      CreateCommandOption::new(CommandOptionType::String, "reason", "Shown in /status")
+        .max_length(200),
+    )
     .add_option(
       CreateCommandOption::new(
-        CommandOptionType::Channel,
-        "channel",
-        "Channel to un/pause the notifications",
+        CommandOptionType::String,
+        "mode",
+        "What happens to items arriving during the pause",
       )
-      .channel_types([ChannelType::Text].to_vec())
-      .required(true),
+      .add_string_choice("Mark them as seen (default)", "skip")
+      .add_string_choice("Queue and post them once resumed", "queue"),
     )
-    .default_member_permissions(Permissions::ADMINISTRATOR)
+    .add_option(instance.description("Id of the instance. All of the channel by default"))
+    .dm_permission(false)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::parse_duration;
+
+  #[test]
+  fn parse_durations() {
+    assert_eq!(parse_duration("30m"), Some(1800));
+    assert_eq!(parse_duration(" 1D12h "), Some(129600));
+    assert_eq!(parse_duration("2w"), Some(1209600));
+  }
+
+  #[test]
+  fn parse_invalid_durations() {
+    assert_eq!(parse_duration(""), None);
+    assert_eq!(parse_duration("6"), None);
+    assert_eq!(parse_duration("h"), None);
+    assert_eq!(parse_duration("6s"), None);
+    assert_eq!(parse_duration("99999999999999999999w"), None);
+    assert_eq!(parse_duration("9999999999999999w"), None);
+    assert_eq!(parse_duration("9223372036854775807m1m"), None);
+  }
 }
@@ -1,4 +1,4 @@
-use serenity::all::{CommandDataOption, CreateCommand, Permissions};
+use serenity::all::{CommandDataOption, CreateCommand};
 
 pub async fn run(_options: &[CommandDataOption]) -> String {
   // For users to check whether the bot is still working.
@@ -6,7 +6,5 @@ pub async fn run(_options: &[CommandDataOption]) -> String {
 }
 
 pub fn register() -> CreateCommand {
-  CreateCommand::new("ping")
-    .description("Wastes bandwidth")
-    .default_member_permissions(Permissions::ADMINISTRATOR)
+  CreateCommand::new("ping").description("Check if the bot is still running")
 }
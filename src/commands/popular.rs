@@ -0,0 +1,69 @@
+use serenity::all::{
+  CommandDataOption, CommandOptionType, ComponentInteraction, CreateCommand, CreateCommandOption,
+  CreateInteractionResponse, CreateInteractionResponseMessage, GuildId,
+};
+
+use super::get_integer_option;
+use crate::announcement::interest_button;
+use crate::database::{get_interest, get_popular, toggle_interest};
+
+pub async fn run(guild_id: Option<GuildId>, options: &[CommandDataOption]) -> String {
+  let Some(guild_id) = guild_id else {
+    return "Popular additions can only be listed within a server.".to_string();
+  };
+  let days = get_integer_option(options, "days").unwrap_or(30);
+  let since = chrono::offset::Utc::now().timestamp() - days * 86400;
+
+  let popular = get_popular(guild_id.get() as i64, since, 10).await;
+  if popular.is_empty() {
+    return format!("No one has shown interest in the additions of the last {days} days.");
+  }
+
+  let mut response = format!("Most wanted additions of the last {days} days:\n");
+  for (position, (title, channel_id, message_id, interest)) in popular.iter().enumerate() {
+    response.push_str(&format!(
+      "{}. [{}](https://discord.com/channels/{}/{}/{}) - {} 👀\n",
+      position + 1,
+      title,
+      guild_id,
+      channel_id,
+      message_id,
+      interest
+    ));
+  }
+  response
+}
+
+/// Handles the interest button below announcements.
+pub async fn interest(component: &ComponentInteraction) -> CreateInteractionResponse {
+  let mut parts = component.data.custom_id.splitn(3, ':').skip(1);
+  let (Some(Ok(instance_id)), Some(item_id)) = (parts.next().map(str::parse::<i64>), parts.next())
+  else {
+    panic!("Discord returned an invalid custom id.")
+  };
+
+  toggle_interest(instance_id, item_id, component.user.id.get() as i64).await;
+  let count = get_interest(instance_id, item_id).await;
+  CreateInteractionResponse::UpdateMessage(
+    CreateInteractionResponseMessage::new().components(vec![interest_button(
+      instance_id,
+      item_id,
+      count,
+    )]),
+  )
+}
+
+pub fn register() -> CreateCommand {
+  CreateCommand::new("popular")
+    .description("List the most wanted recent additions")
+    .add_option(
+      CreateCommandOption::new(
+        CommandOptionType::Integer,
+        "days",
+        "How far to look back. Defaults to 30 days",
+      )
+      .min_int_value(1)
+      .max_int_value(365),
+    )
+    .dm_permission(false)
+}
@@ -0,0 +1,200 @@
+use serenity::all::{
+  CommandDataOption, CommandInteraction, CommandOptionType, Context, CreateAutocompleteResponse,
+  CreateCommand, CreateCommandOption, CreateInteractionResponse, CreateInteractionResponseMessage,
+  EditInteractionResponse, GuildId,
+};
+
+use super::{
+  find_item, get_string_option, get_subcommand, instance_options, item_choices, resolve_instance,
+};
+use crate::announcement::Announcement;
+use crate::database::{get_filters, get_instances_by_guild};
+use crate::{
+  Instance, Item, Type, View, announcement, get_item, get_library_items, get_serialized_page,
+  get_views,
+};
+
+const TYPES: &str = "Movie,Series,Season,Episode";
+
+/// Fetching the seasons and episodes of a series can take a while, so the response is deferred.
+pub async fn run(ctx: &Context, command: &CommandInteraction) {
+  if let Err(why) = command
+    .create_response(
+      &ctx.http,
+      CreateInteractionResponse::Defer(CreateInteractionResponseMessage::new().ephemeral(true)),
+    )
+    .await
+  {
+    println!("Cannot respond to slash command: {}", why);
+    return;
+  }
+  let (subcommand, options) = get_subcommand(&command.data.options);
+  let builder = match subcommand {
+    "item" => item(command.guild_id, options).await,
+    "test" => EditInteractionResponse::new().content(test(ctx, options).await),
+    _ => panic!("Discord returned invalid command options."),
+  };
+  if let Err(why) = command.edit_response(&ctx.http, builder).await {
+    println!("Cannot edit the preview response: {}", why);
+  }
+}
+
+/// Renders an item the way the poller would if it had just been added.
+async fn item(guild_id: Option<GuildId>, options: &[CommandDataOption]) -> EditInteractionResponse {
+  let response = EditInteractionResponse::new();
+  let Some(guild_id) = guild_id else {
+    return response.content("Items can only be previewed within a server.");
+  };
+  let Some(value) = get_string_option(options, "item") else {
+    panic!("Discord returned invalid command options.")
+  };
+  let instances = get_instances_by_guild(guild_id.get() as i64).await;
+  let Some((server, mut item)) = find_item(&instances, &value, TYPES).await else {
+    return response.content("Item could not be found.");
+  };
+  item.LibraryId = library_of(server, &item.Id).await;
+
+  // Seasons and series are announced together with their new children, as if all of them
+  // were new.
+  let (mut seasons, mut episodes) = (vec![], vec![]);
+  if item.Type == Type::Series || item.Type == Type::Season {
+    let Ok(children) = get_library_items(server, Some(&item.Id)).await else {
+      return response.content("Failed to retrieve the item from the server.");
+    };
+    for child in children {
+      match child.Type {
+        Type::Season => seasons.push(child),
+        Type::Episode | Type::Special
+          if child.SeasonId.is_some() && child.IndexNumber.is_some() =>
+        {
+          episodes.push(child)
+        },
+        _ => {},
+      }
+    }
+    if item.Type == Type::Season {
+      seasons = vec![item.clone()];
+    }
+    episodes.sort_by_key(|episode| (episode.ParentIndexNumber, episode.IndexNumber));
+  }
+  // Rules also look at the series of episodes and seasons.
+  let series = match &item.SeriesId {
+    Some(series_id) => get_item(server, series_id).await.ok(),
+    None => None,
+  };
+  let mut itemlist = vec![item];
+  let all_items: Vec<Item> = itemlist
+    .iter()
+    .chain(&seasons)
+    .chain(&episodes)
+    .chain(&series)
+    .cloned()
+    .collect();
+  let Some(announcement) =
+    announcement::render(server, &mut itemlist, &all_items, &seasons, &episodes)
+  else {
+    return response.content("This item wouldn't be announced, since it has no media streams.");
+  };
+
+  let (channel_id, roles) = announcement::destination(server, &announcement).await;
+  let mut content = format!("Would be posted to <#{channel_id}>");
+  if !roles.is_empty() {
+    content.push_str(&format!(
+      ", mentioning {}",
+      roles
+        .iter()
+        .map(|role| format!("<@&{role}>"))
+        .collect::<Vec<String>>()
+        .join(" ")
+    ));
+  }
+  content.push('.');
+  if let Some(filter) = announcement.filtered_by(&get_filters(server.id).await, &all_items) {
+    content.push_str(&format!(
+      "\nIt would be skipped due to the filter: {} {}",
+      filter.kind, filter.value
+    ));
+  }
+  response.content(content).embeds(announcement.embeds())
+}
+
+/// Returns the library (user view) an item belongs to.
+async fn library_of(server: &Instance, item_id: &str) -> Option<String> {
+  let views = get_views(server).await.ok()?;
+  let ancestors: Vec<View> = get_serialized_page(format!(
+    "{}/Items/{}/Ancestors?userId={}&api_key={}",
+    server.domain, item_id, server.user_id, server.token
+  ))
+  .await
+  .ok()?;
+  ancestors
+    .into_iter()
+    .find(|ancestor| views.iter().any(|view| view.Id == ancestor.Id))
+    .map(|ancestor| ancestor.Id)
+}
+
+/// Posts a test announcement through the same routes, mentions, threads and webhook as real
+/// ones, to check the permissions of the bot or webhook. It isn't added to the history.
+async fn test(ctx: &Context, options: &[CommandDataOption]) -> String {
+  let server = match resolve_instance(options).await {
+    Ok(server) => server,
+    Err(why) => return why,
+  };
+  let Ok(item) = serde_json::from_value::<Item>(serde_json::json!({
+    "Name": "Test announcement",
+    "Id": "test",
+    "Type": "Movie",
+  })) else {
+    return "Failed to create the test announcement.".to_string();
+  };
+  let announcement = Announcement {
+    item,
+    kind: Type::Movie,
+    ids: vec![],
+    title: "Test announcement".to_string(),
+    image: format!("{}/web/assets/img/banner-light.png", server.domain),
+    description: Some(format!(
+      "New items of {} will be announced like this.",
+      server.domain
+    )),
+    fields: vec![],
+    series: None,
+  };
+  match announcement::deliver_unrecorded(ctx, &server, &announcement).await {
+    Ok(message) => format!("The test announcement has been posted: {}", message.link()),
+    Err(why) => format!("Failed to post the test announcement: {why}"),
+  }
+}
+
+/// Suggests items of the server's instances matching what has been typed so far.
+pub async fn autocomplete(guild_id: Option<GuildId>, value: &str) -> CreateAutocompleteResponse {
+  item_choices(guild_id, value, TYPES).await
+}
+
+pub fn register() -> CreateCommand {
+  let [channel, instance] = instance_options();
+  CreateCommand::new("preview")
+    .description("Show how an item would be announced, or post a test announcement")
+    .add_option(
+      CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "item",
+        "Show how an item would be announced",
+      )
+      .add_sub_option(
+        CreateCommandOption::new(CommandOptionType::String, "item", "Item to preview")
+          .set_autocomplete(true)
+          .required(true),
+      ),
+    )
+    .add_option(
+      CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "test",
+        "Post a test announcement to check the permissions",
+      )
+      .add_sub_option(channel)
+      .add_sub_option(instance),
+    )
+    .dm_permission(false)
+}
@@ -0,0 +1,111 @@
+use serenity::all::{
+  CommandDataOption, CommandDataOptionValue, CommandOptionType, CreateCommand, CreateCommandOption,
+};
+
+use super::{get_option, get_string_option, get_subcommand, instance_options, resolve_instance};
+use crate::database::get_database;
+use crate::digest::{parse_time, parse_timezone};
+
+pub async fn run(options: &[CommandDataOption]) -> String {
+  let (subcommand, options) = get_subcommand(options);
+  let server = match resolve_instance(options).await {
+    Ok(server) => server,
+    Err(why) => return why,
+  };
+
+  match subcommand {
+    "set" => {
+      let (Some(start), Some(end)) = (
+        get_string_option(options, "start"),
+        get_string_option(options, "end"),
+      ) else {
+        panic!("Discord returned invalid command options.")
+      };
+      let timezone = get_string_option(options, "timezone").unwrap_or("UTC".to_string());
+      let collapse = matches!(
+        get_option(options, "collapse"),
+        Some(CommandDataOptionValue::Boolean(true))
+      );
+      if parse_time(&start).is_none() || parse_time(&end).is_none() {
+        return "Times have to look like \"23:00\".".to_string();
+      }
+      if parse_timezone(&timezone).is_none() {
+        return "The timezone has to be a name like \"Europe/Berlin\" or an offset to UTC like \"+02:00\".".to_string();
+      }
+
+      let database = get_database().await;
+      sqlx::query!(
+        "UPDATE FRONT SET Quiet_Start=?, Quiet_End=?, Quiet_Timezone=?, Quiet_Collapse=? WHERE ID=?",
+        start,
+        end,
+        timezone,
+        collapse,
+        server.id
+      )
+      .execute(&database)
+      .await
+      .expect("update error");
+      database.close().await;
+      format!(
+        "Announcements between {start} and {end} ({timezone}) are now held back and posted {} afterwards.",
+        if collapse {
+          "as one summary"
+        } else {
+          "one by one"
+        }
+      )
+    },
+    "off" => {
+      let database = get_database().await;
+      sqlx::query!(
+        "UPDATE FRONT SET Quiet_Start=NULL, Quiet_End=NULL, Quiet_Timezone=NULL, Quiet_Collapse=0 WHERE ID=?",
+        server.id
+      )
+      .execute(&database)
+      .await
+      .expect("update error");
+      database.close().await;
+      "Successfully removed the quiet hours.".to_string()
+    },
+    _ => panic!("Discord returned invalid command options."),
+  }
+}
+
+pub fn register() -> CreateCommand {
+  let [channel, instance] = instance_options();
+  CreateCommand::new("quiet")
+    .description("Hold back announcements during the night")
+    .add_option(
+      CreateCommandOption::new(CommandOptionType::SubCommand, "set", "Set the quiet hours")
+        .add_sub_option(channel.clone())
+        .add_sub_option(
+          CreateCommandOption::new(CommandOptionType::String, "start", "e.g. \"23:00\"")
+            .required(true),
+        )
+        .add_sub_option(
+          CreateCommandOption::new(CommandOptionType::String, "end", "e.g. \"07:00\"")
+            .required(true),
+        )
+        .add_sub_option(CreateCommandOption::new(
+          CommandOptionType::String,
+          "timezone",
+          "Name like \"Europe/Berlin\" or offset to UTC like \"+02:00\". Defaults to UTC",
+        ))
+        .add_sub_option(CreateCommandOption::new(
+          CommandOptionType::Boolean,
+          "collapse",
+          "Post held back announcements as one summary",
+        ))
+        .add_sub_option(instance.clone()),
+    )
+    .add_option(
+      CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "off",
+        "Remove the quiet hours",
+      )
+      .add_sub_option(channel)
+      .add_sub_option(instance),
+    )
+    .dm_permission(false)
+}
@@ -1,43 +1,129 @@
 use serenity::all::{
-  ChannelType, CommandDataOption, CommandDataOptionValue, CommandOptionType, CreateCommand,
-  CreateCommandOption, Permissions,
+  ButtonStyle, CommandDataOption, CommandOptionType, ComponentInteraction, CreateActionRow,
+  CreateButton, CreateCommand, CreateCommandOption, CreateInteractionResponse,
+  CreateInteractionResponseMessage, Permissions,
 };
 
-pub async fn run(options: &[CommandDataOption]) -> String {
-  let database = sqlx::sqlite::SqlitePoolOptions::new()
-    .max_connections(5)
-    .connect_with(
-      sqlx::sqlite::SqliteConnectOptions::new()
-        .filename("jellycord.sqlite")
-        .create_if_missing(true),
+use super::{Access, get_string_option, instance_options, is_allowed, resolve_instances};
+use crate::Instance;
+use crate::database::{archive_seen, delete_instance, get_instances_by_channel, purge_seen};
+
+/// Days the seen items of archived instances are kept.
+pub const ARCHIVE_DAYS: i64 = 30;
+
+/// Asks for confirmation before anything gets deleted.
+pub async fn run(options: &[CommandDataOption]) -> CreateInteractionResponseMessage {
+  let message = CreateInteractionResponseMessage::new();
+  let instances = match resolve_instances(options).await {
+    Ok(instances) => instances,
+    Err(why) => return message.content(why),
+  };
+  let mode = get_string_option(options, "mode").unwrap_or("archive".to_string());
+  let list = instances
+    .iter()
+    .map(|instance| {
+      format!(
+        "- `{}` {} ({})",
+        instance.id,
+        instance.domain,
+        instance.user_name.as_ref().unwrap_or(&instance.user_id)
+      )
+    })
+    .collect::<Vec<String>>()
+    .join("\n");
+  let consequence = if mode == "delete" {
+    "Everything, including which items have already been announced, will be deleted.".to_string()
+  } else {
+    format!(
+      "Which items have already been announced and the announcement history are kept for {ARCHIVE_DAYS} days, so setting the server up again in this channel won't repeat them or lose `/history`."
     )
-    .await
-    .expect("Couldn't connect to database");
+  };
 
-  let channel_id = match options.get(0).unwrap().value {
-    CommandDataOptionValue::Channel(integer) => integer.get() as i64,
-    _ => {
-      panic!("Discord returned invalid command options.")
+  let channel_id = instances[0].channel_id;
+  let selected = match instances.as_slice() {
+    [instance] => instance.id,
+    _ => 0,
+  };
+  message
+    .content(format!(
+      "This will reset the following instance(s) of <#{channel_id}>:\n{list}\n{consequence}"
+    ))
+    .components(vec![CreateActionRow::Buttons(vec![
+      CreateButton::new(format!("reset:{mode}:{channel_id}:{selected}"))
+        .label("Reset")
+        .style(ButtonStyle::Danger),
+      CreateButton::new("reset:cancel")
+        .label("Cancel")
+        .style(ButtonStyle::Secondary),
+    ])])
+}
+
+/// Handles the buttons of [`run`].
+pub async fn confirm(component: &ComponentInteraction) -> CreateInteractionResponse {
+  let message = CreateInteractionResponseMessage::new().components(vec![]);
+  if !is_allowed(component.member.as_ref(), Access::Admin).await {
+    return CreateInteractionResponse::Message(
+      CreateInteractionResponseMessage::new()
+        .content("Only administrators can reset instances.")
+        .ephemeral(true),
+    );
+  }
+
+  let parts: Vec<&str> = component.data.custom_id.split(':').collect();
+  let (mode, channel_id, selected) = match parts.as_slice() {
+    [_, "cancel"] => {
+      return CreateInteractionResponse::UpdateMessage(message.content("Reset cancelled."));
     },
+    [_, mode, channel_id, selected] => match (channel_id.parse::<i64>(), selected.parse::<i64>()) {
+      (Ok(channel_id), Ok(selected)) => (*mode, channel_id, selected),
+      _ => panic!("Discord returned an invalid custom id."),
+    },
+    _ => panic!("Discord returned an invalid custom id."),
   };
 
-  sqlx::query!("DELETE FROM FRONT WHERE Channel_ID=?", channel_id)
-    .execute(&database)
+  let instances: Vec<Instance> = get_instances_by_channel(channel_id)
     .await
-    .expect("dump error");
-
-  database.close().await;
+    .into_iter()
+    .filter(|instance| selected == 0 || instance.id == selected)
+    .collect();
+  if instances.is_empty() {
+    return CreateInteractionResponse::UpdateMessage(
+      message.content("These instances don't exist anymore."),
+    );
+  }
 
-  "Successfully reset channel.".to_string()
+  let now = chrono::offset::Utc::now().timestamp();
+  for server in &instances {
+    delete_instance(server, mode == "delete").await;
+    if mode == "delete" {
+      purge_seen(&server.user_id).await;
+    } else {
+      archive_seen(&server.user_id, now).await;
+    }
+  }
+  CreateInteractionResponse::UpdateMessage(message.content(format!(
+    "Successfully reset {} instance(s) of <#{channel_id}>.",
+    instances.len()
+  )))
 }
 
 pub fn register() -> CreateCommand {
+  let [channel, instance] = instance_options();
   CreateCommand::new("reset")
-    .description("Reset a channel")
+    .description("Break jellyfin connection for the current channel")
+    .add_option(channel)
     .add_option(
-      CreateCommandOption::new(CommandOptionType::Channel, "channel", "Channel to reset")
-        .channel_types([ChannelType::Text].to_vec())
-        .required(true),
+      CreateCommandOption::new(
+        CommandOptionType::String,
+        "mode",
+        "What happens to the record of announced items",
+      )
+      .add_string_choice(
+        format!("Keep it for {ARCHIVE_DAYS} days (default)"),
+        "archive",
+      )
+      .add_string_choice("Delete everything", "delete"),
     )
+    .add_option(instance.description("Id of the instance. All of the channel by default"))
     .default_member_permissions(Permissions::ADMINISTRATOR)
 }
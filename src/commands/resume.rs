@@ -0,0 +1,47 @@
+use serenity::all::{CommandDataOption, CreateCommand};
+
+use super::{instance_options, resolve_instances};
+use crate::database::{get_pending, resume_instance};
+
+pub async fn run(options: &[CommandDataOption]) -> String {
+  let instances = match resolve_instances(options).await {
+    Ok(instances) => instances,
+    Err(why) => return why,
+  };
+  let paused: Vec<_> = instances
+    .iter()
+    .filter(|instance| instance.active_channel == 0)
+    .collect();
+  if paused.is_empty() {
+    return "This channel isn't paused.".to_string();
+  }
+
+  let mut queued = 0;
+  for server in &paused {
+    resume_instance(server.id).await;
+    if server.pause_queue {
+      queued += get_pending(server.id).await.len();
+    }
+  }
+
+  let mut response = if paused.len() == 1 {
+    "Successfully resumed the instance.".to_string()
+  } else {
+    format!("Successfully resumed {} instances.", paused.len())
+  };
+  if queued > 0 {
+    response.push_str(&format!(
+      "\n{queued} queued announcement(s) will be posted with the next check."
+    ));
+  }
+  response
+}
+
+pub fn register() -> CreateCommand {
+  let [channel, instance] = instance_options();
+  CreateCommand::new("resume")
+    .description("Resume announcements of a paused channel")
+    .add_option(channel)
+    .add_option(instance.description("Id of the instance. All of the channel by default"))
+    .dm_permission(false)
+}
@@ -0,0 +1,244 @@
+use std::collections::HashSet;
+
+use serenity::all::{
+  ButtonStyle, CommandDataOption, CommandInteraction, CommandOptionType, ComponentInteraction,
+  Context, CreateActionRow, CreateButton, CreateCommand, CreateCommandOption, CreateEmbed,
+  CreateInteractionResponse, CreateInteractionResponseMessage, EditInteractionResponse,
+};
+
+use super::{Access, get_subcommand, instance_options, is_allowed, resolve_instance};
+use crate::database::{
+  get_instance, get_library_by_user, mark_seen, set_last_error, set_valve_state,
+};
+use crate::{Instance, Item, Type, announcement, get_serialized_library};
+
+/// Fetching the whole library takes longer than discord waits, so the response is deferred.
+pub async fn run(ctx: &Context, command: &CommandInteraction) {
+  if let Err(why) = command
+    .create_response(
+      &ctx.http,
+      CreateInteractionResponse::Defer(CreateInteractionResponseMessage::new().ephemeral(true)),
+    )
+    .await
+  {
+    println!("Cannot respond to slash command: {}", why);
+    return;
+  }
+  let content = resync(&command.data.options).await;
+  if let Err(why) = command
+    .edit_response(&ctx.http, EditInteractionResponse::new().content(content))
+    .await
+  {
+    println!("Cannot edit the resync response: {}", why);
+  }
+}
+
+async fn resync(options: &[CommandDataOption]) -> String {
+  let (subcommand, options) = get_subcommand(options);
+  let server = match resolve_instance(options).await {
+    Ok(server) => server,
+    Err(why) => return why,
+  };
+  let Ok(unseen) = get_unseen(&server).await else {
+    return "Failed to retrieve the library from the server.".to_string();
+  };
+
+  match subcommand {
+    "dry-run" => describe(&unseen),
+    "apply" => {
+      mark_seen(
+        &server.user_id,
+        &unseen
+          .iter()
+          .map(|item| item.Id.clone())
+          .collect::<Vec<String>>(),
+      )
+      .await;
+      set_valve_state(server.id, 0).await;
+      format!(
+        "Marked {} new items as seen. Nothing has been announced.",
+        unseen.len()
+      )
+    },
+    _ => panic!("Discord returned invalid command options."),
+  }
+}
+
+/// Returns the items of the server that haven't been seen yet.
+async fn get_unseen(server: &Instance) -> Result<Vec<Item>, ()> {
+  let library = get_serialized_library(server).await?;
+  let seen: HashSet<String> = get_library_by_user(server.user_id.clone())
+    .await
+    .into_iter()
+    .collect();
+  Ok(
+    library
+      .Items
+      .into_iter()
+      .filter(|item| !seen.contains(&item.Id))
+      .collect(),
+  )
+}
+
+/// Summarizes what the next check would announce, before filters are applied.
+fn describe(unseen: &[Item]) -> String {
+  if unseen.is_empty() {
+    return "There are no new items, nothing would be announced.".to_string();
+  }
+  let count = |types: &[Type]| {
+    unseen
+      .iter()
+      .filter(|item| types.contains(&item.Type))
+      .count()
+  };
+  let mut response = format!(
+    "{} new items would be announced with the next check: {} movies, {} series, {} seasons, {} episodes.",
+    unseen.len(),
+    count(&[Type::Movie]),
+    count(&[Type::Series]),
+    count(&[Type::Season]),
+    count(&[Type::Episode, Type::Special])
+  );
+  for item in unseen.iter().take(10) {
+    response.push_str(&format!("\n- {item}"));
+  }
+  if unseen.len() > 10 {
+    response.push_str(&format!("\n- … and {} more", unseen.len() - 10));
+  }
+  response
+}
+
+/// Asks the admins what to do with an unusually large number of new items.
+pub async fn alert(ctx: &Context, server: &Instance, count: usize) {
+  let embed = CreateEmbed::new()
+    .title("Announcements held back")
+    .description(format!(
+      "{count} new items were found in a single check, more than the configured limit. This usually happens after the server re-scanned its library.\nNothing will be announced until a manager decides."
+    ))
+    .color(0xe67e22);
+  let buttons = CreateActionRow::Buttons(vec![
+    CreateButton::new(format!("valve:{}:announce", server.id))
+      .label("Announce them")
+      .style(ButtonStyle::Danger),
+    CreateButton::new(format!("valve:{}:seen", server.id))
+      .label("Mark them as seen")
+      .style(ButtonStyle::Primary),
+  ]);
+  set_last_error(
+    server.id,
+    chrono::offset::Utc::now().timestamp(),
+    &format!("{count} new items are held back, waiting for a manager."),
+  )
+  .await;
+  // Sent by the bot itself, since webhooks that weren't created by it can't carry the buttons.
+  let bot = Instance {
+    webhook_url: None,
+    ..server.clone()
+  };
+  match announcement::post(
+    ctx,
+    &bot,
+    "Announcements held back",
+    vec![embed],
+    vec![buttons],
+  )
+  .await
+  {
+    // Without the buttons the items would be held back until someone finds `/resync apply`.
+    Ok(message) if !message.components.is_empty() => set_valve_state(server.id, 1).await,
+    Ok(_) => eprintln!("The safety valve alert was sent without its buttons"),
+    Err(why) => eprintln!("Error sending the safety valve alert: {why:?}"),
+  }
+}
+
+/// Handles the buttons of [`alert`].
+pub async fn valve(ctx: &Context, component: &ComponentInteraction) {
+  let is_manager = is_allowed(component.member.as_ref(), Access::Manager).await;
+  let response = if is_manager {
+    CreateInteractionResponse::Acknowledge
+  } else {
+    CreateInteractionResponse::Message(
+      CreateInteractionResponseMessage::new()
+        .content("Only managers can decide about held back announcements.")
+        .ephemeral(true),
+    )
+  };
+  if let Err(why) = component.create_response(&ctx.http, response).await {
+    println!("Cannot respond to component: {}", why);
+    return;
+  }
+  if !is_manager {
+    return;
+  }
+
+  let mut parts = component.data.custom_id.split(':').skip(1);
+  let (Some(Ok(id)), Some(action)) = (parts.next().map(str::parse::<i64>), parts.next()) else {
+    panic!("Discord returned an invalid custom id.")
+  };
+  let mut decided = true;
+  let content = match get_instance(id).await {
+    None => "This instance doesn't exist anymore.".to_string(),
+    Some(server) if server.valve_state != 1 => "This has already been decided.".to_string(),
+    Some(server) if action == "announce" => {
+      set_valve_state(server.id, 2).await;
+      format!(
+        "<@{}> confirmed, the items will be announced with the next check.",
+        component.user.id
+      )
+    },
+    Some(server) => match get_unseen(&server).await {
+      Ok(unseen) => {
+        mark_seen(
+          &server.user_id,
+          &unseen
+            .iter()
+            .map(|item| item.Id.clone())
+            .collect::<Vec<String>>(),
+        )
+        .await;
+        set_valve_state(server.id, 0).await;
+        format!(
+          "<@{}> marked {} items as seen, nothing has been announced.",
+          component.user.id,
+          unseen.len()
+        )
+      },
+      Err(_) => {
+        decided = false;
+        "Failed to retrieve the library from the server, please try again.".to_string()
+      },
+    },
+  };
+  let mut builder = EditInteractionResponse::new().content(content);
+  if decided {
+    builder = builder.components(vec![]);
+  }
+  if let Err(why) = component.edit_response(&ctx.http, builder).await {
+    println!("Cannot edit the safety valve alert: {}", why);
+  }
+}
+
+pub fn register() -> CreateCommand {
+  let [channel, instance] = instance_options();
+  CreateCommand::new("resync")
+    .description("Mark the library as seen without announcing it, or preview the next check")
+    .add_option(
+      CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "apply",
+        "Mark every new item as seen without announcing it",
+      )
+      .add_sub_option(channel.clone())
+      .add_sub_option(instance.clone()),
+    )
+    .add_option(
+      CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "dry-run",
+        "Show how many items the next check would announce",
+      )
+      .add_sub_option(channel)
+      .add_sub_option(instance),
+    )
+    .dm_permission(false)
+}
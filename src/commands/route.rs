@@ -0,0 +1,177 @@
+use serenity::all::{CommandDataOption, CommandOptionType, CreateCommand, CreateCommandOption};
+
+use super::{
+  ANNOUNCEMENT_CHANNELS, get_channel_option, get_integer_option, get_string_option, get_subcommand,
+  instance_options, resolve_instance,
+};
+use crate::database::{get_database, get_routes};
+use crate::{Instance, get_views};
+
+pub async fn run(options: &[CommandDataOption]) -> String {
+  let (subcommand, options) = get_subcommand(options);
+  let server = match resolve_instance(options).await {
+    Ok(server) => server,
+    Err(why) => return why,
+  };
+
+  match subcommand {
+    "add" => add(&server, options).await,
+    "list" => list(&server).await,
+    "remove" => remove(&server, options).await,
+    _ => panic!("Discord returned invalid command options."),
+  }
+}
+
+async fn add(server: &Instance, options: &[CommandDataOption]) -> String {
+  let (Some(kind), Some(value), Some(target)) = (
+    get_string_option(options, "kind"),
+    get_string_option(options, "value"),
+    get_channel_option(options, "target"),
+  ) else {
+    panic!("Discord returned invalid command options.")
+  };
+  let value = value.trim().to_string();
+
+  let value = match kind.as_str() {
+    "type" => match ["Movie", "Series", "Season", "Episode"]
+      .iter()
+      .find(|t| t.eq_ignore_ascii_case(&value))
+    {
+      Some(t) => t.to_string(),
+      None => return "Type must be one of: Movie, Series, Season, Episode".to_string(),
+    },
+    "library" => {
+      let Ok(views) = get_views(server).await else {
+        return "Failed to retrieve the libraries from the server.".to_string();
+      };
+      match views
+        .iter()
+        .find(|view| view.Name.eq_ignore_ascii_case(&value) || view.Id == value)
+      {
+        Some(view) => view.Id.clone(),
+        None => {
+          return format!(
+            "Library could not be found. Available: {}",
+            views
+              .iter()
+              .map(|view| view.Name.as_str())
+              .collect::<Vec<&str>>()
+              .join(", ")
+          );
+        },
+      }
+    },
+    _ => value,
+  };
+
+  let database = get_database().await;
+  sqlx::query!(
+    "INSERT INTO ROUTES (Instance_ID, Kind, Value, Channel_ID) VALUES (?1, ?2, ?3, ?4)",
+    server.id,
+    kind,
+    value,
+    target
+  )
+  .execute(&database)
+  .await
+  .expect("insert error");
+  database.close().await;
+
+  "Successfully added route.".to_string()
+}
+
+async fn list(server: &Instance) -> String {
+  let routes = get_routes(server.id).await;
+  if routes.is_empty() {
+    return format!("No routes. Everything is sent to <#{}>.", server.channel_id);
+  }
+  let views = get_views(server).await.unwrap_or_default();
+
+  let mut content = String::new();
+  for route in routes {
+    let value = match views.iter().find(|view| view.Id == route.value) {
+      Some(view) if route.kind == "library" => view.Name.clone(),
+      _ => route.value,
+    };
+    content.push_str(&format!(
+      "`{}` {} \"{}\" → <#{}>\n",
+      route.id, route.kind, value, route.channel_id
+    ));
+  }
+  content.push_str(&format!("Everything else → <#{}>", server.channel_id));
+  content
+}
+
+async fn remove(server: &Instance, options: &[CommandDataOption]) -> String {
+  let Some(id) = get_integer_option(options, "id") else {
+    panic!("Discord returned invalid command options.")
+  };
+
+  let database = get_database().await;
+  let result = sqlx::query!(
+    "DELETE FROM ROUTES WHERE ID=? AND Instance_ID=?",
+    id,
+    server.id
+  )
+  .execute(&database)
+  .await
+  .expect("delete error");
+  database.close().await;
+
+  if result.rows_affected() == 0 {
+    "Route could not be found.".to_string()
+  } else {
+    "Successfully removed route.".to_string()
+  }
+}
+
+pub fn register() -> CreateCommand {
+  let [channel, instance] = instance_options();
+  CreateCommand::new("route")
+    .description("Send announcements to other channels by type, library, genre or tag")
+    .add_option(
+      CreateCommandOption::new(CommandOptionType::SubCommand, "add", "Add a route")
+        .add_sub_option(channel.clone())
+        .add_sub_option(
+          CreateCommandOption::new(CommandOptionType::String, "kind", "What to match on")
+            .add_string_choice("Type (Movie, Series, Season, Episode)", "type")
+            .add_string_choice("Library", "library")
+            .add_string_choice("Genre", "genre")
+            .add_string_choice("Tag", "tag")
+            .required(true),
+        )
+        .add_sub_option(
+          CreateCommandOption::new(
+            CommandOptionType::String,
+            "value",
+            "Type, library name, genre or tag to match",
+          )
+          .required(true),
+        )
+        .add_sub_option(
+          CreateCommandOption::new(
+            CommandOptionType::Channel,
+            "target",
+            "Channel to receive the matching announcements",
+          )
+          .channel_types(ANNOUNCEMENT_CHANNELS.to_vec())
+          .required(true),
+        )
+        .add_sub_option(instance.clone()),
+    )
+    .add_option(
+      CreateCommandOption::new(CommandOptionType::SubCommand, "list", "List all routes")
+        .add_sub_option(channel.clone())
+        .add_sub_option(instance.clone()),
+    )
+    .add_option(
+      CreateCommandOption::new(CommandOptionType::SubCommand, "remove", "Remove a route")
+        .add_sub_option(channel)
+        .add_sub_option(
+          CreateCommandOption::new(CommandOptionType::Integer, "id", "Id of the route")
+            .required(true),
+        )
+        .add_sub_option(instance),
+    )
+    .dm_permission(false)
+}
@@ -0,0 +1,107 @@
+use serenity::all::{
+  CommandDataOption, CommandOptionType, ComponentInteraction, CreateAutocompleteResponse,
+  CreateCommand, CreateCommandOption, CreateInteractionResponse, CreateInteractionResponseMessage,
+  GuildId,
+};
+
+use super::{get_string_option, item_page, resolve_guild_instance};
+use crate::database::get_instance;
+use crate::{Instance, search_items};
+
+const TYPES: &str = "Movie,Series,Episode";
+
+/// Queries end up in the custom id of the buttons, which is limited to 100 characters.
+const MAX_QUERY_LENGTH: usize = 60;
+
+pub async fn run(
+  guild_id: Option<GuildId>,
+  options: &[CommandDataOption],
+) -> CreateInteractionResponseMessage {
+  let server = match resolve_guild_instance(guild_id, options).await {
+    Ok(server) => server,
+    Err(why) => return CreateInteractionResponseMessage::new().content(why),
+  };
+  let Some(query) = get_string_option(options, "query") else {
+    panic!("Discord returned invalid command options.")
+  };
+  page(&server, query.trim(), 0).await
+}
+
+/// Handles the buttons switching between the results of `/search`.
+pub async fn turn(component: &ComponentInteraction) -> CreateInteractionResponse {
+  let mut parts = component.data.custom_id.splitn(4, ':').skip(1);
+  let (Some(Ok(id)), Some(Ok(page_index)), Some(query)) = (
+    parts.next().map(str::parse::<i64>),
+    parts.next().map(str::parse::<usize>),
+    parts.next(),
+  ) else {
+    panic!("Discord returned an invalid custom id.")
+  };
+  let message = match get_instance(id).await {
+    Some(server) => page(&server, query, page_index).await,
+    None => CreateInteractionResponseMessage::new()
+      .content("This instance doesn't exist anymore.")
+      .embeds(vec![])
+      .components(vec![]),
+  };
+  CreateInteractionResponse::UpdateMessage(message)
+}
+
+async fn page(
+  server: &Instance,
+  query: &str,
+  page_index: usize,
+) -> CreateInteractionResponseMessage {
+  let Ok(items) = search_items(server, query, TYPES).await else {
+    return CreateInteractionResponseMessage::new()
+      .content("Failed to search the library of the server.");
+  };
+  item_page(server, items, page_index, |page_index| {
+    format!("search:{}:{}:{}", server.id, page_index, query)
+  })
+}
+
+/// Suggests items of the instance matching what has been typed so far.
+pub async fn autocomplete(
+  guild_id: Option<GuildId>,
+  options: &[CommandDataOption],
+  value: &str,
+) -> CreateAutocompleteResponse {
+  let mut response = CreateAutocompleteResponse::new();
+  if value.trim().is_empty() {
+    return response;
+  }
+  let Ok(server) = resolve_guild_instance(guild_id, options).await else {
+    return response;
+  };
+  let Ok(results) = search_items(&server, value.trim(), TYPES).await else {
+    return response;
+  };
+  for item in results {
+    let name: String = item.Name.chars().take(MAX_QUERY_LENGTH).collect();
+    response =
+      response.add_string_choice(item.to_string().chars().take(100).collect::<String>(), name);
+  }
+  response
+}
+
+pub fn register() -> CreateCommand {
+  CreateCommand::new("search")
+    .description("Search the library")
+    .add_option(
+      CreateCommandOption::new(
+        CommandOptionType::String,
+        "query",
+        "Name of a movie, series or episode",
+      )
+      .set_autocomplete(true)
+      .max_length(MAX_QUERY_LENGTH as u16)
+      .required(true),
+    )
+    .add_option(CreateCommandOption::new(
+      CommandOptionType::Integer,
+      "instance",
+      "Id of the instance, if the server has several",
+    ))
+    .dm_permission(false)
+}
@@ -0,0 +1,228 @@
+use std::collections::{HashMap, HashSet};
+
+use serenity::all::{
+  CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption, CreateEmbed,
+  CreateInteractionResponse, CreateInteractionResponseMessage, EditInteractionResponse,
+};
+
+use super::get_integer_option;
+use crate::announcement::Announcement;
+use crate::database::{get_announced, get_instances_by_guild};
+use crate::{Instance, Item, Type, get_serialized_library};
+
+/// Discord accepts up to 10 embeds per message.
+const MAX_EMBEDS: usize = 10;
+
+/// How many weeks the chart of additions covers.
+const WEEKS: i64 = 8;
+
+/// Fetching whole libraries takes longer than discord waits, so the response is deferred.
+pub async fn run(ctx: &Context, command: &CommandInteraction) {
+  if let Err(why) = command
+    .create_response(
+      &ctx.http,
+      CreateInteractionResponse::Defer(CreateInteractionResponseMessage::new().ephemeral(true)),
+    )
+    .await
+  {
+    println!("Cannot respond to slash command: {}", why);
+    return;
+  }
+
+  let response = EditInteractionResponse::new();
+  let builder = match command.guild_id {
+    None => response.content("Statistics can only be shown within a server."),
+    Some(guild_id) => {
+      let mut instances = get_instances_by_guild(guild_id.get() as i64).await;
+      if let Some(id) = get_integer_option(&command.data.options, "instance") {
+        instances.retain(|instance| instance.id == id);
+      }
+      if instances.is_empty() {
+        response.content("There are no instances in this server. Use `/init` to add one.")
+      } else {
+        let mut embeds = vec![];
+        for server in instances.iter().take(MAX_EMBEDS) {
+          embeds.push(stats(server).await);
+        }
+        response.embeds(embeds)
+      }
+    },
+  };
+  if let Err(why) = command.edit_response(&ctx.http, builder).await {
+    println!("Cannot edit the stats response: {}", why);
+  }
+}
+
+async fn stats(server: &Instance) -> CreateEmbed {
+  let embed = CreateEmbed::new().title(&server.domain);
+  let Ok(library) = get_serialized_library(server).await else {
+    return embed.description("Failed to retrieve the library from the server.");
+  };
+  let items = library.Items;
+  let count = |types: &[Type]| {
+    items
+      .iter()
+      .filter(|item| types.contains(&item.Type))
+      .count()
+  };
+  let playable: Vec<&Item> = items
+    .iter()
+    .filter(|item| matches!(item.Type, Type::Movie | Type::Episode | Type::Special))
+    .collect();
+  let runtime: u64 = playable.iter().filter_map(|item| item.RunTimeTicks).sum();
+
+  let now = chrono::offset::Utc::now().timestamp();
+  let announced = get_announced(server.id, now - (WEEKS * 7).max(30) * 86400).await;
+  let episodes: HashSet<&str> = items
+    .iter()
+    .filter(|item| matches!(item.Type, Type::Episode | Type::Special))
+    .map(|item| item.Id.as_str())
+    .collect();
+  // Episodes announced twice, like in a digest and then on their own, are only counted once.
+  let announced_episodes = |since: i64| -> usize {
+    announced
+      .iter()
+      .filter(|(created, _, _)| *created >= since)
+      .filter_map(|(_, _, json)| serde_json::from_str::<Announcement>(json.as_deref()?).ok())
+      .flat_map(|announcement| announcement.ids)
+      .filter(|id| episodes.contains(id.as_str()))
+      .collect::<HashSet<String>>()
+      .len()
+  };
+
+  embed
+    .field(
+      "Items",
+      format!(
+        "{} movies, {} series, {} seasons, {} episodes",
+        count(&[Type::Movie]),
+        count(&[Type::Series]),
+        count(&[Type::Season]),
+        count(&[Type::Episode, Type::Special])
+      ),
+      false,
+    )
+    .field("Total runtime", format_duration(runtime), true)
+    .field(
+      "Episodes added",
+      format!(
+        "{} this week\n{} this month",
+        announced_episodes(now - 7 * 86400),
+        announced_episodes(now - 30 * 86400)
+      ),
+      true,
+    )
+    .field("Resolutions", resolutions(&playable), false)
+    .field("Audio languages", languages(&playable), false)
+    .field("Additions per week", chart(&announced, now), false)
+}
+
+/// Formats runtime ticks as days and hours.
+fn format_duration(ticks: u64) -> String {
+  let hours = ticks / 10_000_000 / 3600;
+  format!("{} days, {} hours", hours / 24, hours % 24)
+}
+
+fn resolutions(items: &[&Item]) -> String {
+  let mut counts = [0; 4];
+  for item in items {
+    let Some(video) = item
+      .MediaStreams
+      .iter()
+      .flatten()
+      .find(|stream| stream.Type == "Video")
+    else {
+      continue;
+    };
+    // Widths are checked first, since cropped movies are less high than their class suggests.
+    let class = match (video.Width, video.Height) {
+      (Some(width), _) if width >= 3200 => 0,
+      (_, Some(height)) if height >= 1600 => 0,
+      (Some(width), _) if width >= 1800 => 1,
+      (_, Some(height)) if height >= 1000 => 1,
+      (Some(width), _) if width >= 1200 => 2,
+      (_, Some(height)) if height >= 700 => 2,
+      _ => 3,
+    };
+    counts[class] += 1;
+  }
+  format!(
+    "4K: {}, 1080p: {}, 720p: {}, SD: {}",
+    counts[0], counts[1], counts[2], counts[3]
+  )
+}
+
+/// Lists the five most common audio languages, counted once per item.
+fn languages(items: &[&Item]) -> String {
+  let mut counts: HashMap<String, usize> = HashMap::new();
+  for item in items {
+    let languages: HashSet<String> = item
+      .MediaStreams
+      .iter()
+      .flatten()
+      .filter(|stream| stream.Type == "Audio")
+      .map(|stream| stream.Language.clone().unwrap_or("?".to_string()))
+      .collect();
+    for language in languages {
+      *counts.entry(language).or_default() += 1;
+    }
+  }
+  let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+  counts.sort_by(|x, y| y.1.cmp(&x.1).then(x.0.cmp(&y.0)));
+  if counts.is_empty() {
+    return "None".to_string();
+  }
+  counts
+    .iter()
+    .take(5)
+    .map(|(language, count)| format!("{language}: {count}"))
+    .collect::<Vec<String>>()
+    .join(", ")
+}
+
+/// Draws a bar per week, oldest first.
+fn chart(announced: &[(i64, String, Option<String>)], now: i64) -> String {
+  let weeks: Vec<(String, usize)> = (0..WEEKS)
+    .rev()
+    .map(|week| {
+      let end = now - week * 7 * 86400;
+      let start = end - 7 * 86400;
+      let label = chrono::DateTime::from_timestamp(start, 0)
+        .map(|date| date.format("%m-%d").to_string())
+        .unwrap_or_default();
+      let count = announced
+        .iter()
+        .filter(|(created, _, _)| *created >= start && *created < end)
+        .map(|(_, item_id, _)| item_id)
+        .collect::<HashSet<&String>>()
+        .len();
+      (label, count)
+    })
+    .collect();
+  let max = weeks
+    .iter()
+    .map(|(_, count)| *count)
+    .max()
+    .unwrap_or(0)
+    .max(1);
+  let mut chart = "```\n".to_string();
+  for (label, count) in weeks {
+    chart.push_str(&format!(
+      "{label} {:<20} {count}\n",
+      "█".repeat(count * 20 / max)
+    ));
+  }
+  chart.push_str("```");
+  chart
+}
+
+pub fn register() -> CreateCommand {
+  CreateCommand::new("stats")
+    .description("Show statistics about the libraries")
+    .add_option(CreateCommandOption::new(
+      CommandOptionType::Integer,
+      "instance",
+      "Id of the instance. All of the server by default",
+    ))
+    .dm_permission(false)
+}
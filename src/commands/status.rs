@@ -0,0 +1,103 @@
+use serenity::all::{
+  CommandDataOption, CreateCommand, CreateEmbed, CreateInteractionResponseMessage, GuildId,
+};
+
+use crate::database::{count_announcements, count_seen, get_instances_by_guild};
+
+/// Discord accepts up to 10 embeds per message.
+const MAX_EMBEDS: usize = 10;
+
+pub async fn run(
+  guild_id: Option<GuildId>,
+  _options: &[CommandDataOption],
+) -> CreateInteractionResponseMessage {
+  let message = CreateInteractionResponseMessage::new();
+  let Some(guild_id) = guild_id else {
+    return message.content("The status can only be shown within a server.");
+  };
+  let instances = get_instances_by_guild(guild_id.get() as i64).await;
+  if instances.is_empty() {
+    return message.content("There are no instances in this server. Use `/init` to add one.");
+  }
+
+  let week_ago = chrono::offset::Utc::now().timestamp() - 7 * 86400;
+  let mut embeds: Vec<CreateEmbed> = vec![];
+  for server in instances.iter().take(MAX_EMBEDS) {
+    let state = if server.active_channel == 1 {
+      "Active".to_string()
+    } else {
+      let mut state = "Paused".to_string();
+      if let Some(until) = server.pause_until {
+        state.push_str(&format!(" until <t:{until}:f>"));
+      }
+      if server.pause_queue {
+        state.push_str(", queueing");
+      }
+      if let Some(reason) = &server.pause_reason {
+        state.push_str(&format!("\n{reason}"));
+      }
+      state
+    };
+    let mut details: Vec<String> = vec![];
+    if let Some(schedule) = &server.digest_schedule {
+      details.push(format!("Digest: `{schedule}`"));
+    }
+    if let (Some(start), Some(end)) = (&server.quiet_start, &server.quiet_end) {
+      details.push(format!("Quiet hours: {start}-{end}"));
+    }
+
+    let user = match &server.user_name {
+      Some(name) => format!("{name} (`{}`)", server.user_id),
+      None => format!("`{}`", server.user_id),
+    };
+    let last_poll = match server.last_poll {
+      Some(timestamp) => format!("<t:{timestamp}:R>"),
+      None => "Never".to_string(),
+    };
+    let last_error = match (&server.last_error, server.last_error_at) {
+      (Some(error), Some(timestamp)) => format!("{error} (<t:{timestamp}:R>)"),
+      _ => "None".to_string(),
+    };
+
+    let mut embed = CreateEmbed::new()
+      .title(format!("Instance {} - {}", server.id, server.domain))
+      .field("Channel", format!("<#{}>", server.channel_id), true)
+      .field("User", user, true)
+      .field("State", state, true)
+      .field("Last poll", last_poll, true)
+      .field(
+        "Known items",
+        count_seen(&server.user_id).await.to_string(),
+        true,
+      )
+      .field(
+        "Announcements (7 days)",
+        count_announcements(server.id, week_ago).await.to_string(),
+        true,
+      )
+      .field("Last error", last_error, false);
+    if !details.is_empty() {
+      embed = embed.description(details.join("\n"));
+    }
+    if server.last_error_at > server.last_poll {
+      embed = embed.color(0xe74c3c);
+    }
+    embeds.push(embed);
+  }
+
+  if instances.len() > MAX_EMBEDS {
+    return message
+      .content(format!(
+        "Showing {MAX_EMBEDS} of {} instances.",
+        instances.len()
+      ))
+      .embeds(embeds);
+  }
+  message.embeds(embeds)
+}
+
+pub fn register() -> CreateCommand {
+  CreateCommand::new("status")
+    .description("Show the state of every instance in this server")
+    .dm_permission(false)
+}
@@ -0,0 +1,62 @@
+use serenity::all::{
+  CommandDataOption, CommandOptionType, Context, CreateAutocompleteResponse, CreateCommand,
+  CreateCommandOption,
+};
+
+use super::get_string_option;
+use crate::database::{get_database, get_follows, get_instance};
+use crate::follows::sync_roles;
+
+pub async fn run(ctx: &Context, user_id: i64, options: &[CommandDataOption]) -> String {
+  let Some(value) = get_string_option(options, "series") else {
+    panic!("Discord returned invalid command options.")
+  };
+  let follows = get_follows(user_id).await;
+  let Some(follow) = follows.iter().find(|follow| {
+    follow.id.to_string() == value || follow.series_name.eq_ignore_ascii_case(value.trim())
+  }) else {
+    return "You aren't following this series.".to_string();
+  };
+
+  let database = get_database().await;
+  sqlx::query!(
+    "DELETE FROM FOLLOWS WHERE ID=? AND User_ID=?",
+    follow.id,
+    user_id
+  )
+  .execute(&database)
+  .await
+  .expect("delete error");
+  database.close().await;
+
+  if let Some(server) = get_instance(follow.instance_id).await {
+    sync_roles(ctx, &server, &follow.series_id, user_id, false).await;
+  }
+  format!("You've stopped following \"{}\".", follow.series_name)
+}
+
+/// Suggests the followed series matching what has been typed so far.
+pub async fn autocomplete(user_id: i64, value: &str) -> CreateAutocompleteResponse {
+  let value = value.trim().to_lowercase();
+  get_follows(user_id)
+    .await
+    .iter()
+    .filter(|follow| follow.series_name.to_lowercase().contains(&value))
+    .take(25)
+    .fold(CreateAutocompleteResponse::new(), |response, follow| {
+      response.add_string_choice(
+        follow.series_name.chars().take(100).collect::<String>(),
+        follow.id.to_string(),
+      )
+    })
+}
+
+pub fn register() -> CreateCommand {
+  CreateCommand::new("unfollow")
+    .description("Stop following a series")
+    .add_option(
+      CreateCommandOption::new(CommandOptionType::String, "series", "Series to unfollow")
+        .set_autocomplete(true)
+        .required(true),
+    )
+}
@@ -0,0 +1,217 @@
+use std::sync::OnceLock;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use ring::aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use ring::rand::{SecureRandom, SystemRandom};
+
+use crate::database::{get_tokens, get_webhook_urls, set_secrets};
+
+/// Prefix of encrypted API keys and webhook URLs. Values without it are stored in plain text.
+const PREFIX: &str = "enc:v1:";
+
+/// The key API keys and webhook URLs are encrypted with, if one has been configured.
+static KEY: OnceLock<Option<LessSafeKey>> = OnceLock::new();
+
+/// Parses a base64 encoded 256-bit key, as printed by `jellycord generate-key`.
+pub fn parse_key(encoded: &str) -> Result<LessSafeKey, String> {
+  let bytes = STANDARD
+    .decode(encoded.trim())
+    .map_err(|_| "The encryption key isn't valid base64.".to_string())?;
+  let key = UnboundKey::new(&AES_256_GCM, &bytes)
+    .map_err(|_| "The encryption key has to be 32 bytes long.".to_string())?;
+  Ok(LessSafeKey::new(key))
+}
+
+pub fn generate_key() -> String {
+  let mut bytes = [0u8; 32];
+  SystemRandom::new()
+    .fill(&mut bytes)
+    .expect("Couldn't generate a key");
+  STANDARD.encode(bytes)
+}
+
+/// Sets the key used by [`encrypt`] and [`decrypt`]. Can only be called once.
+pub fn init(key: Option<LessSafeKey>) {
+  if KEY.set(key).is_err() {
+    panic!("The encryption key has already been set.");
+  }
+}
+
+fn key() -> Option<&'static LessSafeKey> {
+  KEY.get().and_then(Option::as_ref)
+}
+
+pub fn is_encrypted(value: &str) -> bool {
+  value.starts_with(PREFIX)
+}
+
+fn encrypt_with(key: &LessSafeKey, plaintext: &str) -> String {
+  let mut nonce = [0u8; NONCE_LEN];
+  SystemRandom::new()
+    .fill(&mut nonce)
+    .expect("Couldn't generate a nonce");
+  let mut data = plaintext.as_bytes().to_vec();
+  key
+    .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut data)
+    .expect("Couldn't encrypt the value");
+  let mut sealed = nonce.to_vec();
+  sealed.append(&mut data);
+  format!("{PREFIX}{}", STANDARD.encode(sealed))
+}
+
+fn decrypt_with(key: Option<&LessSafeKey>, value: &str) -> Result<String, String> {
+  let Some(encoded) = value.strip_prefix(PREFIX) else {
+    return Ok(value.to_string());
+  };
+  let Some(key) = key else {
+    return Err(
+      "An API key or webhook URL is encrypted, but no encryption key has been configured."
+        .to_string(),
+    );
+  };
+  let mut sealed = STANDARD
+    .decode(encoded)
+    .map_err(|_| "An encrypted API key or webhook URL is corrupted.".to_string())?;
+  if sealed.len() < NONCE_LEN {
+    return Err("An encrypted API key or webhook URL is corrupted.".to_string());
+  }
+  let mut data = sealed.split_off(NONCE_LEN);
+  let nonce = Nonce::try_assume_unique_for_key(&sealed).expect("nonce has the right length");
+  let plaintext = key
+    .open_in_place(nonce, Aad::empty(), &mut data)
+    .map_err(|_| {
+      "An API key or webhook URL couldn't be decrypted. Is the encryption key correct?".to_string()
+    })?;
+  String::from_utf8(plaintext.to_vec())
+    .map_err(|_| "A decrypted API key or webhook URL isn't valid text.".to_string())
+}
+
+/// Encrypts an API key or webhook URL for storage. Stays in plain text if no key has been configured.
+pub fn encrypt(plaintext: &str) -> String {
+  match key() {
+    Some(key) => encrypt_with(key, plaintext),
+    None => plaintext.to_string(),
+  }
+}
+
+pub fn decrypt(value: &str) -> Result<String, String> {
+  decrypt_with(key(), value)
+}
+
+/// Encrypts the API keys and webhook URLs still stored in plain text and checks that all others
+/// can be decrypted.
+pub async fn encrypt_stored_tokens() -> Result<(), String> {
+  let tokens = get_tokens().await;
+  let webhook_urls = get_webhook_urls().await;
+  for (_, value) in tokens.iter().chain(&webhook_urls) {
+    decrypt(value)?;
+  }
+  if key().is_none() {
+    if !tokens.is_empty() {
+      println!(
+        "Warning: API keys and webhook URLs are stored in plain text. Set `encryption_key` to encrypt them (see `jellycord generate-key`)."
+      );
+    }
+    return Ok(());
+  }
+
+  let plaintext = |values: Vec<(i64, String)>| -> Vec<(i64, String)> {
+    values
+      .into_iter()
+      .filter(|(_, value)| !is_encrypted(value))
+      .map(|(id, value)| (id, encrypt(&value)))
+      .collect()
+  };
+  let tokens = plaintext(tokens);
+  let webhook_urls = plaintext(webhook_urls);
+  if !tokens.is_empty() || !webhook_urls.is_empty() {
+    set_secrets(&tokens, &webhook_urls).await;
+    println!(
+      "Encrypted {} stored API key(s) and {} webhook URL(s).",
+      tokens.len(),
+      webhook_urls.len()
+    );
+  }
+  Ok(())
+}
+
+/// Re-encrypts every stored API key and webhook URL with a new key. Returns how many values
+/// have been re-encrypted.
+pub async fn rotate(old: Option<&LessSafeKey>, new: &LessSafeKey) -> Result<usize, String> {
+  let reencrypt = |values: Vec<(i64, String)>| -> Result<Vec<(i64, String)>, String> {
+    values
+      .into_iter()
+      .map(|(id, value)| Ok((id, encrypt_with(new, &decrypt_with(old, &value)?))))
+      .collect()
+  };
+  // Everything is decrypted before anything is written, so a wrong key doesn't leave a mix.
+  let tokens = reencrypt(get_tokens().await)?;
+  let webhook_urls = reencrypt(get_webhook_urls().await)?;
+  set_secrets(&tokens, &webhook_urls).await;
+  Ok(tokens.len() + webhook_urls.len())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn new_key() -> LessSafeKey {
+    parse_key(&generate_key()).unwrap()
+  }
+
+  #[test]
+  fn round_trip() {
+    let key = new_key();
+    let sealed = encrypt_with(&key, "secret api key");
+    assert!(is_encrypted(&sealed));
+    assert!(!sealed.contains("secret"));
+    assert_eq!(
+      decrypt_with(Some(&key), &sealed),
+      Ok("secret api key".to_string())
+    );
+    // Every encryption uses a new nonce.
+    assert_ne!(sealed, encrypt_with(&key, "secret api key"));
+  }
+
+  #[test]
+  fn wrong_key() {
+    let sealed = encrypt_with(&new_key(), "secret api key");
+    assert!(decrypt_with(Some(&new_key()), &sealed).is_err());
+    assert!(decrypt_with(None, &sealed).is_err());
+  }
+
+  #[test]
+  fn plaintext_passthrough() {
+    assert_eq!(
+      decrypt_with(Some(&new_key()), "plain api key"),
+      Ok("plain api key".to_string())
+    );
+    assert_eq!(
+      decrypt_with(None, "https://discord.com/api/webhooks/1/token"),
+      Ok("https://discord.com/api/webhooks/1/token".to_string())
+    );
+  }
+
+  #[test]
+  fn corrupt_input() {
+    let key = new_key();
+    let sealed = encrypt_with(&key, "secret api key");
+    let truncated = &sealed[..sealed.len() - 8];
+    assert!(decrypt_with(Some(&key), truncated).is_err());
+    assert!(decrypt_with(Some(&key), PREFIX).is_err());
+    assert!(decrypt_with(Some(&key), &format!("{PREFIX}AAAA")).is_err());
+    assert!(decrypt_with(Some(&key), &format!("{PREFIX}not base64!")).is_err());
+    let mut flipped = STANDARD.decode(&sealed[PREFIX.len()..]).unwrap();
+    let last = flipped.len() - 1;
+    flipped[last] ^= 1;
+    let flipped = format!("{PREFIX}{}", STANDARD.encode(flipped));
+    assert!(decrypt_with(Some(&key), &flipped).is_err());
+  }
+
+  #[test]
+  fn invalid_keys() {
+    assert!(parse_key("not base64!").is_err());
+    assert!(parse_key(&STANDARD.encode([0u8; 16])).is_err());
+  }
+}
@@ -1,9 +1,10 @@
-use sqlx::Row;
+use sqlx::{Row, SqlitePool};
 
-use crate::Instance;
+use crate::announcement::Announcement;
+use crate::{Filter, Follow, HistoryEntry, Instance, Mention, Route};
 
-pub async fn get_front_database() -> Vec<Instance> {
-  let database = sqlx::sqlite::SqlitePoolOptions::new()
+pub async fn get_database() -> SqlitePool {
+  sqlx::sqlite::SqlitePoolOptions::new()
     .max_connections(5)
     .connect_with(
       sqlx::sqlite::SqliteConnectOptions::new()
@@ -11,34 +12,94 @@ pub async fn get_front_database() -> Vec<Instance> {
         .create_if_missing(true),
     )
     .await
-    .expect("Couldn't connect to database");
-  let db = sqlx::query!("SELECT * FROM FRONT WHERE Active_Channel = 1")
+    .expect("Couldn't connect to database")
+}
+
+/// Returns every instance, including paused ones.
+///
+/// Instances whose API key or webhook URL can't be decrypted, e.g. after a wrong key has been
+/// configured, are left out and get the error recorded instead.
+pub async fn get_instances() -> Vec<Instance> {
+  let database = get_database().await;
+  let mut undecryptable: Vec<(i64, String)> = vec![];
+  let db = sqlx::query!("SELECT * FROM FRONT")
     .fetch_all(&database)
     .await
     .unwrap()
     .iter()
-    .map(|row| Instance {
-      active_channel: row.Active_Channel,
-      channel_id: row.Channel_ID,
-      domain: row.Domain.clone(),
-      token: row.Token.clone(),
-      user_id: row.UserID.clone(),
+    .filter_map(|row| {
+      let secrets = crate::crypto::decrypt(&row.Token).and_then(|token| {
+        let webhook_url = row
+          .Webhook_URL
+          .as_deref()
+          .map(crate::crypto::decrypt)
+          .transpose()?;
+        Ok((token, webhook_url))
+      });
+      let (token, webhook_url) = match secrets {
+        Ok(secrets) => secrets,
+        Err(why) => {
+          eprintln!("Skipping instance {}: {why}", row.ID);
+          if row.Last_Error.as_deref() != Some(why.as_str()) {
+            undecryptable.push((row.ID, why));
+          }
+          return None;
+        },
+      };
+      Some(Instance {
+        id: row.ID,
+        active_channel: row.Active_Channel,
+        channel_id: row.Channel_ID,
+        domain: row.Domain.clone(),
+        token,
+        user_id: row.UserID.clone(),
+        webhook_url,
+        webhook_username: row.Webhook_Username.clone(),
+        webhook_avatar: row.Webhook_Avatar.clone(),
+        library_mode: row.Library_Mode,
+        digest_schedule: row.Digest_Schedule.clone(),
+        digest_timezone: row.Digest_Timezone.clone(),
+        digest_last: row.Digest_Last,
+        quiet_start: row.Quiet_Start.clone(),
+        quiet_end: row.Quiet_End.clone(),
+        quiet_timezone: row.Quiet_Timezone.clone(),
+        quiet_collapse: row.Quiet_Collapse == 1,
+        guild_id: row.Guild_ID,
+        user_name: row.User_Name.clone(),
+        last_poll: row.Last_Poll,
+        last_error: row.Last_Error.clone(),
+        last_error_at: row.Last_Error_At,
+        pause_until: row.Pause_Until,
+        pause_reason: row.Pause_Reason.clone(),
+        pause_queue: row.Pause_Queue == 1,
+        valve_state: row.Valve_State,
+      })
     })
     .collect();
   database.close().await;
+  for (id, why) in undecryptable {
+    set_last_error(id, chrono::offset::Utc::now().timestamp(), &why).await;
+  }
   db
 }
 
-pub async fn get_library_by_user(user_id: String) -> Vec<String> {
-  let database = sqlx::sqlite::SqlitePoolOptions::new()
-    .max_connections(5)
-    .connect_with(
-      sqlx::sqlite::SqliteConnectOptions::new()
-        .filename("jellycord.sqlite")
-        .create_if_missing(true),
-    )
+pub async fn get_instance(id: i64) -> Option<Instance> {
+  get_instances()
     .await
-    .expect("Couldn't connect to database");
+    .into_iter()
+    .find(|instance| instance.id == id)
+}
+
+pub async fn get_instances_by_channel(channel_id: i64) -> Vec<Instance> {
+  get_instances()
+    .await
+    .into_iter()
+    .filter(|instance| instance.channel_id == channel_id)
+    .collect()
+}
+
+pub async fn get_library_by_user(user_id: String) -> Vec<String> {
+  let database = get_database().await;
   let db_fetch = sqlx::query(format!("SELECT {:?} FROM LIBRARY", &user_id).as_str())
     .fetch_all(&database)
     .await
@@ -50,5 +111,959 @@ pub async fn get_library_by_user(user_id: String) -> Vec<String> {
     items.append(&mut vec![id]);
   }
 
+  database.close().await;
   items
 }
+
+/// Adds the given item ids to the seen-column of a user.
+pub async fn mark_seen(user_id: &str, ids: &[String]) {
+  if ids.is_empty() {
+    return;
+  }
+  let database = get_database().await;
+  let values = ids
+    .iter()
+    .map(|id| format!("(\"{}\")", id))
+    .collect::<Vec<String>>()
+    .join(",");
+  sqlx::query(format!("INSERT INTO LIBRARY ({:?}) VALUES {}", user_id, values).as_str())
+    .execute(&database)
+    .await
+    .expect("insert error");
+  database.close().await;
+}
+
+/// Returns the forum post that was created for a series in a forum channel.
+pub async fn get_thread(channel_id: i64, series_id: &str) -> Option<i64> {
+  let database = get_database().await;
+  let thread = sqlx::query!(
+    "SELECT Thread_ID FROM THREADS WHERE Channel_ID=? AND SeriesID=?",
+    channel_id,
+    series_id
+  )
+  .fetch_optional(&database)
+  .await
+  .expect("select error")
+  .map(|row| row.Thread_ID);
+  database.close().await;
+  thread
+}
+
+pub async fn set_thread(channel_id: i64, series_id: &str, thread_id: i64) {
+  let database = get_database().await;
+  sqlx::query!(
+    "INSERT INTO THREADS (Channel_ID, SeriesID, Thread_ID) VALUES (?1, ?2, ?3)",
+    channel_id,
+    series_id,
+    thread_id
+  )
+  .execute(&database)
+  .await
+  .expect("insert error");
+  database.close().await;
+}
+
+pub async fn remove_thread(channel_id: i64, series_id: &str) {
+  let database = get_database().await;
+  sqlx::query!(
+    "DELETE FROM THREADS WHERE Channel_ID=? AND SeriesID=?",
+    channel_id,
+    series_id
+  )
+  .execute(&database)
+  .await
+  .expect("delete error");
+  database.close().await;
+}
+
+pub async fn get_routes(instance_id: i64) -> Vec<Route> {
+  let database = get_database().await;
+  let routes = sqlx::query!(
+    "SELECT * FROM ROUTES WHERE Instance_ID=? ORDER BY ID",
+    instance_id
+  )
+  .fetch_all(&database)
+  .await
+  .expect("select error")
+  .iter()
+  .map(|row| Route {
+    id: row.ID,
+    kind: row.Kind.clone(),
+    value: row.Value.clone(),
+    channel_id: row.Channel_ID,
+  })
+  .collect();
+  database.close().await;
+  routes
+}
+
+/// Returns the libraries listed for an instance. See `Instance::library_mode`.
+pub async fn get_libraries(instance_id: i64) -> Vec<String> {
+  let database = get_database().await;
+  let libraries = sqlx::query!(
+    "SELECT LibraryID FROM LIBRARIES WHERE Instance_ID=?",
+    instance_id
+  )
+  .fetch_all(&database)
+  .await
+  .expect("select error")
+  .into_iter()
+  .map(|row| row.LibraryID)
+  .collect();
+  database.close().await;
+  libraries
+}
+
+pub async fn set_libraries(instance_id: i64, mode: i64, library_ids: &[String]) {
+  let database = get_database().await;
+  sqlx::query!(
+    "UPDATE FRONT SET Library_Mode=? WHERE ID=?",
+    mode,
+    instance_id
+  )
+  .execute(&database)
+  .await
+  .expect("update error");
+  sqlx::query!("DELETE FROM LIBRARIES WHERE Instance_ID=?", instance_id)
+    .execute(&database)
+    .await
+    .expect("delete error");
+  for library_id in library_ids {
+    sqlx::query!(
+      "INSERT INTO LIBRARIES (Instance_ID, LibraryID) VALUES (?1, ?2)",
+      instance_id,
+      library_id
+    )
+    .execute(&database)
+    .await
+    .expect("insert error");
+  }
+  database.close().await;
+}
+
+pub async fn get_filters(instance_id: i64) -> Vec<Filter> {
+  let database = get_database().await;
+  let filters = sqlx::query!(
+    "SELECT * FROM FILTERS WHERE Instance_ID=? ORDER BY ID",
+    instance_id
+  )
+  .fetch_all(&database)
+  .await
+  .expect("select error")
+  .iter()
+  .map(|row| Filter {
+    id: row.ID,
+    kind: row.Kind.clone(),
+    value: row.Value.clone(),
+  })
+  .collect();
+  database.close().await;
+  filters
+}
+
+/// Queues an announcement to be posted later, e.g. with the next digest.
+pub async fn queue_announcement(instance_id: i64, announcement: &Announcement) {
+  let database = get_database().await;
+  let json = serde_json::to_string(announcement).unwrap();
+  let now = chrono::offset::Utc::now().timestamp();
+  sqlx::query!(
+    "INSERT INTO PENDING (Instance_ID, Announcement, Created) VALUES (?1, ?2, ?3)",
+    instance_id,
+    json,
+    now
+  )
+  .execute(&database)
+  .await
+  .expect("insert error");
+  database.close().await;
+}
+
+/// Returns the queued announcements of an instance, oldest first.
+pub async fn get_pending(instance_id: i64) -> Vec<(i64, Announcement)> {
+  let database = get_database().await;
+  let pending = sqlx::query!(
+    "SELECT ID, Announcement FROM PENDING WHERE Instance_ID=? ORDER BY ID",
+    instance_id
+  )
+  .fetch_all(&database)
+  .await
+  .expect("select error")
+  .into_iter()
+  .filter_map(|row| match serde_json::from_str(&row.Announcement) {
+    Ok(announcement) => Some((row.ID, announcement)),
+    Err(why) => {
+      eprintln!("Dropping queued announcement {}: {}", row.ID, why);
+      None
+    },
+  })
+  .collect();
+  database.close().await;
+  pending
+}
+
+pub async fn remove_pending(ids: &[i64]) {
+  let database = get_database().await;
+  for id in ids {
+    sqlx::query!("DELETE FROM PENDING WHERE ID=?", id)
+      .execute(&database)
+      .await
+      .expect("delete error");
+  }
+  database.close().await;
+}
+
+pub async fn set_digest_last(instance_id: i64, timestamp: i64) {
+  let database = get_database().await;
+  sqlx::query!(
+    "UPDATE FRONT SET Digest_Last=? WHERE ID=?",
+    timestamp,
+    instance_id
+  )
+  .execute(&database)
+  .await
+  .expect("update error");
+  database.close().await;
+}
+
+pub async fn get_mentions(instance_id: i64) -> Vec<Mention> {
+  let database = get_database().await;
+  let mentions = sqlx::query!(
+    "SELECT * FROM MENTIONS WHERE Instance_ID=? ORDER BY ID",
+    instance_id
+  )
+  .fetch_all(&database)
+  .await
+  .expect("select error")
+  .iter()
+  .map(|row| Mention {
+    id: row.ID,
+    kind: row.Kind.clone(),
+    value: row.Value.clone(),
+    role_id: row.Role_ID,
+  })
+  .collect();
+  database.close().await;
+  mentions
+}
+
+pub async fn get_instances_by_guild(guild_id: i64) -> Vec<Instance> {
+  get_instances()
+    .await
+    .into_iter()
+    .filter(|instance| instance.guild_id == Some(guild_id))
+    .collect()
+}
+
+pub async fn set_guild(instance_id: i64, guild_id: i64) {
+  let database = get_database().await;
+  sqlx::query!(
+    "UPDATE FRONT SET Guild_ID=? WHERE ID=?",
+    guild_id,
+    instance_id
+  )
+  .execute(&database)
+  .await
+  .expect("update error");
+  database.close().await;
+}
+
+pub async fn get_follows(user_id: i64) -> Vec<Follow> {
+  let database = get_database().await;
+  let follows = sqlx::query!(
+    r#"SELECT ID AS "ID!", User_ID, Instance_ID, Series_ID, Series_Name FROM FOLLOWS WHERE User_ID=? ORDER BY Series_Name"#,
+    user_id
+  )
+  .fetch_all(&database)
+  .await
+  .expect("select error")
+  .iter()
+  .map(|row| Follow {
+    id: row.ID,
+    user_id: row.User_ID,
+    instance_id: row.Instance_ID,
+    series_id: row.Series_ID.clone(),
+    series_name: row.Series_Name.clone(),
+  })
+  .collect();
+  database.close().await;
+  follows
+}
+
+/// Returns the members following a series who haven't disabled direct messages.
+pub async fn get_followers(instance_id: i64, series_id: &str) -> Vec<i64> {
+  let database = get_database().await;
+  let followers = sqlx::query!(
+    "SELECT FOLLOWS.User_ID FROM FOLLOWS LEFT JOIN DM_SETTINGS ON FOLLOWS.User_ID=DM_SETTINGS.User_ID WHERE Instance_ID=? AND Series_ID=? AND IFNULL(DM_SETTINGS.Enabled, 1)=1",
+    instance_id,
+    series_id
+  )
+  .fetch_all(&database)
+  .await
+  .expect("select error")
+  .iter()
+  .map(|row| row.User_ID)
+  .collect();
+  database.close().await;
+  followers
+}
+
+/// Returns every member following a series, including those who disabled direct messages.
+pub async fn get_all_followers(instance_id: i64, series_id: &str) -> Vec<i64> {
+  let database = get_database().await;
+  let followers = sqlx::query!(
+    "SELECT User_ID FROM FOLLOWS WHERE Instance_ID=? AND Series_ID=?",
+    instance_id,
+    series_id
+  )
+  .fetch_all(&database)
+  .await
+  .expect("select error")
+  .iter()
+  .map(|row| row.User_ID)
+  .collect();
+  database.close().await;
+  followers
+}
+
+pub async fn get_dms_enabled(user_id: i64) -> bool {
+  let database = get_database().await;
+  let enabled = sqlx::query!("SELECT Enabled FROM DM_SETTINGS WHERE User_ID=?", user_id)
+    .fetch_optional(&database)
+    .await
+    .expect("select error")
+    .is_none_or(|row| row.Enabled == 1);
+  database.close().await;
+  enabled
+}
+
+pub async fn set_dms_enabled(user_id: i64, enabled: bool) {
+  let database = get_database().await;
+  sqlx::query!(
+    "INSERT INTO DM_SETTINGS (User_ID, Enabled) VALUES (?1, ?2) ON CONFLICT(User_ID) DO UPDATE SET Enabled=?2",
+    user_id,
+    enabled
+  )
+  .execute(&database)
+  .await
+  .expect("update error");
+  database.close().await;
+}
+
+/// Counts a direct message against the hourly limit of a member.
+///
+/// Returns `false` without counting it if the limit has already been reached.
+pub async fn take_dm_quota(user_id: i64, now: i64, limit: i64) -> bool {
+  let database = get_database().await;
+  let row = sqlx::query!(
+    "SELECT Window_Start, Sent FROM DM_SETTINGS WHERE User_ID=?",
+    user_id
+  )
+  .fetch_optional(&database)
+  .await
+  .expect("select error");
+  let (window_start, sent) = match row {
+    Some(row) if now - row.Window_Start < 3600 => (row.Window_Start, row.Sent),
+    _ => (now, 0),
+  };
+  let allowed = sent < limit;
+  if allowed {
+    let sent = sent + 1;
+    sqlx::query!(
+      "INSERT INTO DM_SETTINGS (User_ID, Window_Start, Sent) VALUES (?1, ?2, ?3) ON CONFLICT(User_ID) DO UPDATE SET Window_Start=?2, Sent=?3",
+      user_id,
+      window_start,
+      sent
+    )
+    .execute(&database)
+    .await
+    .expect("update error");
+  }
+  database.close().await;
+  allowed
+}
+
+/// Records the delivery of an announcement. `message` is the channel and message it has been
+/// posted as, or `None` if sending it failed. Earlier failures of the same item are replaced,
+/// since the poller retries them every cycle.
+pub async fn record_announcement(
+  instance_id: i64,
+  announcement: &Announcement,
+  message: Option<(i64, i64)>,
+) {
+  let database = get_database().await;
+  let now = chrono::offset::Utc::now().timestamp();
+  let kind = announcement.kind.to_string();
+  let json = serde_json::to_string(announcement).unwrap();
+  let (channel_id, message_id) = message.unzip();
+  let status = if message.is_some() {
+    "posted"
+  } else {
+    "failed"
+  };
+  let mut transaction = database.begin().await.expect("transaction error");
+  sqlx::query!(
+    "DELETE FROM ANNOUNCEMENTS WHERE Instance_ID=? AND Item_ID=? AND Status='failed'",
+    instance_id,
+    announcement.item.Id
+  )
+  .execute(&mut *transaction)
+  .await
+  .expect("delete error");
+  sqlx::query!(
+    "INSERT INTO ANNOUNCEMENTS (Instance_ID, Item_ID, Title, Type, Channel_ID, Message_ID, Created, Status, Announcement) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+    instance_id,
+    announcement.item.Id,
+    announcement.title,
+    kind,
+    channel_id,
+    message_id,
+    now,
+    status,
+    json
+  )
+  .execute(&mut *transaction)
+  .await
+  .expect("insert error");
+  transaction.commit().await.expect("commit error");
+  database.close().await;
+}
+
+/// Returns the announcements of a guild's instances since a timestamp, newest first.
+pub async fn get_history(guild_id: i64, since: i64) -> Vec<HistoryEntry> {
+  let database = get_database().await;
+  let history = sqlx::query!(
+    "SELECT ANNOUNCEMENTS.ID, ANNOUNCEMENTS.Instance_ID, ANNOUNCEMENTS.Title, ANNOUNCEMENTS.Type, ANNOUNCEMENTS.Channel_ID, ANNOUNCEMENTS.Message_ID, ANNOUNCEMENTS.Created, ANNOUNCEMENTS.Status, ANNOUNCEMENTS.Announcement
+    FROM ANNOUNCEMENTS
+    JOIN FRONT ON FRONT.ID=ANNOUNCEMENTS.Instance_ID
+    WHERE FRONT.Guild_ID=? AND ANNOUNCEMENTS.Created>=?
+    ORDER BY ANNOUNCEMENTS.ID DESC",
+    guild_id,
+    since
+  )
+  .fetch_all(&database)
+  .await
+  .expect("select error")
+  .into_iter()
+  .map(|row| HistoryEntry {
+    id: row.ID,
+    instance_id: row.Instance_ID,
+    title: row.Title,
+    kind: row.Type,
+    channel_id: row.Channel_ID,
+    message_id: row.Message_ID,
+    created: row.Created,
+    status: row.Status,
+    announcement: row.Announcement,
+  })
+  .collect();
+  database.close().await;
+  history
+}
+
+/// Returns a single announcement of a guild's instances.
+pub async fn get_history_entry(guild_id: i64, id: i64) -> Option<HistoryEntry> {
+  get_history(guild_id, 0)
+    .await
+    .into_iter()
+    .find(|entry| entry.id == id)
+}
+
+/// Sets the status of every announcement posted as a message. Digests contain several.
+pub async fn set_message_status(message_id: i64, status: &str) {
+  let database = get_database().await;
+  sqlx::query!(
+    "UPDATE ANNOUNCEMENTS SET Status=? WHERE Message_ID=?",
+    status,
+    message_id
+  )
+  .execute(&database)
+  .await
+  .expect("update error");
+  database.close().await;
+}
+
+/// Returns how many members want to watch an item.
+pub async fn get_interest(instance_id: i64, item_id: &str) -> i64 {
+  let database = get_database().await;
+  let count = sqlx::query!(
+    "SELECT COUNT(*) AS Count FROM INTEREST WHERE Instance_ID=? AND Item_ID=?",
+    instance_id,
+    item_id
+  )
+  .fetch_one(&database)
+  .await
+  .expect("select error")
+  .Count;
+  database.close().await;
+  count
+}
+
+/// Adds or removes the interest of a member and returns whether they're now interested.
+pub async fn toggle_interest(instance_id: i64, item_id: &str, user_id: i64) -> bool {
+  let database = get_database().await;
+  let removed = sqlx::query!(
+    "DELETE FROM INTEREST WHERE Instance_ID=? AND Item_ID=? AND User_ID=?",
+    instance_id,
+    item_id,
+    user_id
+  )
+  .execute(&database)
+  .await
+  .expect("delete error")
+  .rows_affected()
+    > 0;
+  if !removed {
+    sqlx::query!(
+      "INSERT INTO INTEREST (Instance_ID, Item_ID, User_ID) VALUES (?1, ?2, ?3)",
+      instance_id,
+      item_id,
+      user_id
+    )
+    .execute(&database)
+    .await
+    .expect("insert error");
+  }
+  database.close().await;
+  !removed
+}
+
+/// Returns the title, channel, message and interest of the most wanted items announced in a
+/// guild since `since`.
+pub async fn get_popular(guild_id: i64, since: i64, limit: i64) -> Vec<(String, i64, i64, i64)> {
+  let database = get_database().await;
+  let popular = sqlx::query!(
+    r#"SELECT ANNOUNCEMENTS.Title AS "Title!", ANNOUNCEMENTS.Channel_ID AS "Channel_ID!", MAX(ANNOUNCEMENTS.Message_ID) AS "Message_ID!: i64", COUNT(DISTINCT INTEREST.User_ID) AS "Interest!: i64"
+    FROM ANNOUNCEMENTS
+    JOIN FRONT ON FRONT.ID=ANNOUNCEMENTS.Instance_ID
+    JOIN INTEREST ON INTEREST.Instance_ID=ANNOUNCEMENTS.Instance_ID AND INTEREST.Item_ID=ANNOUNCEMENTS.Item_ID
+    WHERE FRONT.Guild_ID=? AND ANNOUNCEMENTS.Created>=? AND ANNOUNCEMENTS.Status='posted'
+    GROUP BY ANNOUNCEMENTS.Instance_ID, ANNOUNCEMENTS.Item_ID
+    ORDER BY 4 DESC, 3 DESC
+    LIMIT ?"#,
+    guild_id,
+    since,
+    limit
+  )
+  .fetch_all(&database)
+  .await
+  .expect("select error")
+  .into_iter()
+  .map(|row| (row.Title, row.Channel_ID, row.Message_ID, row.Interest))
+  .collect();
+  database.close().await;
+  popular
+}
+
+/// Returns the API keys of all instances as stored, which may be encrypted.
+pub async fn get_tokens() -> Vec<(i64, String)> {
+  let database = get_database().await;
+  let tokens = sqlx::query!("SELECT ID, Token FROM FRONT")
+    .fetch_all(&database)
+    .await
+    .expect("select error")
+    .into_iter()
+    .map(|row| (row.ID, row.Token))
+    .collect();
+  database.close().await;
+  tokens
+}
+
+/// Returns the webhook URLs of all instances that post through one, as stored.
+pub async fn get_webhook_urls() -> Vec<(i64, String)> {
+  let database = get_database().await;
+  let urls = sqlx::query!(
+    r#"SELECT ID, Webhook_URL AS "Webhook_URL!" FROM FRONT WHERE Webhook_URL IS NOT NULL"#
+  )
+  .fetch_all(&database)
+  .await
+  .expect("select error")
+  .into_iter()
+  .map(|row| (row.ID, row.Webhook_URL))
+  .collect();
+  database.close().await;
+  urls
+}
+
+/// Replaces the stored API keys and webhook URLs of several instances in a single transaction,
+/// so either all or none of them are written.
+pub async fn set_secrets(tokens: &[(i64, String)], webhook_urls: &[(i64, String)]) {
+  let database = get_database().await;
+  let mut transaction = database.begin().await.expect("transaction error");
+  for (id, token) in tokens {
+    sqlx::query!("UPDATE FRONT SET Token=? WHERE ID=?", token, id)
+      .execute(&mut *transaction)
+      .await
+      .expect("update error");
+  }
+  for (id, url) in webhook_urls {
+    sqlx::query!("UPDATE FRONT SET Webhook_URL=? WHERE ID=?", url, id)
+      .execute(&mut *transaction)
+      .await
+      .expect("update error");
+  }
+  transaction.commit().await.expect("commit error");
+  database.close().await;
+}
+
+pub async fn set_user_name(instance_id: i64, user_name: &str) {
+  let database = get_database().await;
+  sqlx::query!(
+    "UPDATE FRONT SET User_Name=? WHERE ID=?",
+    user_name,
+    instance_id
+  )
+  .execute(&database)
+  .await
+  .expect("update error");
+  database.close().await;
+}
+
+pub async fn set_last_poll(instance_id: i64, timestamp: i64) {
+  let database = get_database().await;
+  sqlx::query!(
+    "UPDATE FRONT SET Last_Poll=? WHERE ID=?",
+    timestamp,
+    instance_id
+  )
+  .execute(&database)
+  .await
+  .expect("update error");
+  database.close().await;
+}
+
+pub async fn set_last_error(instance_id: i64, timestamp: i64, error: &str) {
+  let database = get_database().await;
+  sqlx::query!(
+    "UPDATE FRONT SET Last_Error=?, Last_Error_At=? WHERE ID=?",
+    error,
+    timestamp,
+    instance_id
+  )
+  .execute(&database)
+  .await
+  .expect("update error");
+  database.close().await;
+}
+
+/// Returns how many items of a user have been marked as seen.
+pub async fn count_seen(user_id: &str) -> i64 {
+  let database = get_database().await;
+  let count = sqlx::query(format!("SELECT COUNT({:?}) FROM LIBRARY", user_id).as_str())
+    .fetch_one(&database)
+    .await
+    .map(|row| row.get::<i64, usize>(0))
+    .unwrap_or(0);
+  database.close().await;
+  count
+}
+
+/// Returns when each announcement of an instance since `since` has been posted, its item and
+/// the serialized announcement if it has been kept. Reposts aren't included.
+pub async fn get_announced(instance_id: i64, since: i64) -> Vec<(i64, String, Option<String>)> {
+  let database = get_database().await;
+  let announced = sqlx::query!(
+    "SELECT Created, Item_ID, Announcement FROM ANNOUNCEMENTS WHERE Instance_ID=? AND Created>=? AND Status!='failed' AND Repost=0",
+    instance_id,
+    since
+  )
+  .fetch_all(&database)
+  .await
+  .expect("select error")
+  .into_iter()
+  .map(|row| (row.Created, row.Item_ID, row.Announcement))
+  .collect();
+  database.close().await;
+  announced
+}
+
+/// Flags the announcement posted as a message as a repost of an earlier one.
+pub async fn set_repost(message_id: i64) {
+  let database = get_database().await;
+  sqlx::query!(
+    "UPDATE ANNOUNCEMENTS SET Repost=1 WHERE Message_ID=?",
+    message_id
+  )
+  .execute(&database)
+  .await
+  .expect("update error");
+  database.close().await;
+}
+
+/// Returns how many announcements an instance has posted since `since`.
+pub async fn count_announcements(instance_id: i64, since: i64) -> i64 {
+  let database = get_database().await;
+  let count = sqlx::query!(
+    "SELECT COUNT(*) AS Count FROM ANNOUNCEMENTS WHERE Instance_ID=? AND Created>=? AND Status!='failed'",
+    instance_id,
+    since
+  )
+  .fetch_one(&database)
+  .await
+  .expect("select error")
+  .Count;
+  database.close().await;
+  count
+}
+
+pub async fn pause_instance(
+  instance_id: i64,
+  until: Option<i64>,
+  reason: Option<&str>,
+  queue: bool,
+) {
+  let database = get_database().await;
+  sqlx::query!(
+    "UPDATE FRONT SET Active_Channel=0, Pause_Until=?, Pause_Reason=?, Pause_Queue=? WHERE ID=?",
+    until,
+    reason,
+    queue,
+    instance_id
+  )
+  .execute(&database)
+  .await
+  .expect("pause error");
+  database.close().await;
+}
+
+pub async fn resume_instance(instance_id: i64) {
+  let database = get_database().await;
+  sqlx::query!(
+    "UPDATE FRONT SET Active_Channel=1, Pause_Until=NULL, Pause_Reason=NULL, Pause_Queue=0 WHERE ID=?",
+    instance_id
+  )
+  .execute(&database)
+  .await
+  .expect("unpause error");
+  database.close().await;
+}
+
+/// Copies the seen items of one user to another, creating the column of the new user if needed.
+pub async fn copy_seen(from_user_id: &str, to_user_id: &str) {
+  let database = get_database().await;
+  sqlx::query(format!("ALTER TABLE LIBRARY ADD {:?} VARCHAR(30)", to_user_id).as_str())
+    .execute(&database)
+    .await
+    .ok();
+  sqlx::query(
+    format!(
+      "INSERT INTO LIBRARY ({:?}) SELECT {:?} FROM LIBRARY WHERE {:?} IS NOT NULL",
+      to_user_id, from_user_id, from_user_id
+    )
+    .as_str(),
+  )
+  .execute(&database)
+  .await
+  .expect("insert error");
+  database.close().await;
+}
+
+/// Deletes an instance along with its settings and queue. Its seen items are kept.
+///
+/// The history and interest of the instance are only deleted if `purge` is set. Otherwise they
+/// are archived along with the seen items, see [`restore_history`].
+pub async fn delete_instance(server: &Instance, purge: bool) {
+  let database = get_database().await;
+  let mut transaction = database.begin().await.expect("transaction error");
+  let mut tables = vec![
+    "ROUTES",
+    "LIBRARIES",
+    "PENDING",
+    "FILTERS",
+    "MENTIONS",
+    "FOLLOWS",
+  ];
+  if purge {
+    tables.extend(["INTEREST", "ANNOUNCEMENTS"]);
+  } else {
+    sqlx::query!(
+      "INSERT INTO ARCHIVED_INSTANCES (Instance_ID, User_ID, Channel_ID) VALUES (?1, ?2, ?3)",
+      server.id,
+      server.user_id,
+      server.channel_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .expect("insert error");
+  }
+  for table in tables {
+    sqlx::query(format!("DELETE FROM {table} WHERE Instance_ID=?").as_str())
+      .bind(server.id)
+      .execute(&mut *transaction)
+      .await
+      .expect("dump error");
+  }
+  sqlx::query!("DELETE FROM FRONT WHERE ID=?", server.id)
+    .execute(&mut *transaction)
+    .await
+    .expect("dump error");
+  // Forum posts are shared by the instances of a channel.
+  sqlx::query!(
+    "DELETE FROM THREADS WHERE Channel_ID=?1 AND NOT EXISTS (SELECT 1 FROM FRONT WHERE Channel_ID=?1)",
+    server.channel_id
+  )
+  .execute(&mut *transaction)
+  .await
+  .expect("dump error");
+  transaction.commit().await.expect("commit error");
+  database.close().await;
+}
+
+/// Hands the history and interest of archived instances of the same user and channel to a new
+/// instance.
+pub async fn restore_history(instance_id: i64, user_id: &str, channel_id: i64) {
+  let database = get_database().await;
+  let mut transaction = database.begin().await.expect("transaction error");
+  for table in ["ANNOUNCEMENTS", "INTEREST"] {
+    sqlx::query(
+      format!(
+        "UPDATE {table} SET Instance_ID=?1 WHERE Instance_ID IN (SELECT Instance_ID FROM ARCHIVED_INSTANCES WHERE User_ID=?2 AND Channel_ID=?3)"
+      )
+      .as_str(),
+    )
+    .bind(instance_id)
+    .bind(user_id)
+    .bind(channel_id)
+    .execute(&mut *transaction)
+    .await
+    .expect("update error");
+  }
+  sqlx::query!(
+    "DELETE FROM ARCHIVED_INSTANCES WHERE User_ID=? AND Channel_ID=?",
+    user_id,
+    channel_id
+  )
+  .execute(&mut *transaction)
+  .await
+  .expect("dump error");
+  transaction.commit().await.expect("commit error");
+  database.close().await;
+}
+
+/// Keeps the seen items of a user for a while after its instance has been reset.
+pub async fn archive_seen(user_id: &str, timestamp: i64) {
+  let database = get_database().await;
+  sqlx::query!(
+    "INSERT INTO ARCHIVE (User_ID, Archived) VALUES (?1, ?2) ON CONFLICT(User_ID) DO UPDATE SET Archived=?2",
+    user_id,
+    timestamp
+  )
+  .execute(&database)
+  .await
+  .expect("insert error");
+  database.close().await;
+}
+
+/// Whether the seen items of a user are archived or still used by an instance.
+pub async fn is_seen_kept(user_id: &str) -> bool {
+  let database = get_database().await;
+  let kept = sqlx::query!(
+    "SELECT User_ID FROM ARCHIVE WHERE User_ID=?1 UNION SELECT UserID FROM FRONT WHERE UserID=?1",
+    user_id
+  )
+  .fetch_optional(&database)
+  .await
+  .expect("select error")
+  .is_some();
+  database.close().await;
+  kept
+}
+
+/// Drops the seen items of a user, including old copies from previous setups, unless an
+/// instance still uses them. The history of its archived instances is dropped as well.
+pub async fn purge_seen(user_id: &str) {
+  let database = get_database().await;
+  let in_use = sqlx::query!("SELECT ID FROM FRONT WHERE UserID=?", user_id)
+    .fetch_optional(&database)
+    .await
+    .expect("select error")
+    .is_some();
+  sqlx::query!("DELETE FROM ARCHIVE WHERE User_ID=?", user_id)
+    .execute(&database)
+    .await
+    .expect("dump error");
+  for table in ["ANNOUNCEMENTS", "INTEREST"] {
+    sqlx::query(
+      format!(
+        "DELETE FROM {table} WHERE Instance_ID IN (SELECT Instance_ID FROM ARCHIVED_INSTANCES WHERE User_ID=?)"
+      )
+      .as_str(),
+    )
+    .bind(user_id)
+    .execute(&database)
+    .await
+    .expect("dump error");
+  }
+  sqlx::query!("DELETE FROM ARCHIVED_INSTANCES WHERE User_ID=?", user_id)
+    .execute(&database)
+    .await
+    .expect("dump error");
+  let columns: Vec<String> = sqlx::query("SELECT name FROM pragma_table_info('LIBRARY')")
+    .fetch_all(&database)
+    .await
+    .expect("select error")
+    .iter()
+    .map(|row| row.get::<String, usize>(0))
+    .filter(|column| (column == user_id && !in_use) || column.starts_with(&format!("{user_id}_")))
+    .collect();
+  for column in columns {
+    sqlx::query(format!("ALTER TABLE LIBRARY DROP COLUMN {:?}", column).as_str())
+      .execute(&database)
+      .await
+      .expect("drop error");
+  }
+  database.close().await;
+}
+
+/// Drops the seen items archived before `before`.
+pub async fn purge_archive(before: i64) {
+  let database = get_database().await;
+  let expired: Vec<String> = sqlx::query!("SELECT User_ID FROM ARCHIVE WHERE Archived<?", before)
+    .fetch_all(&database)
+    .await
+    .expect("select error")
+    .into_iter()
+    .filter_map(|row| row.User_ID)
+    .collect();
+  database.close().await;
+  for user_id in expired {
+    purge_seen(&user_id).await;
+  }
+}
+
+pub async fn set_valve_state(instance_id: i64, state: i64) {
+  let database = get_database().await;
+  sqlx::query!(
+    "UPDATE FRONT SET Valve_State=? WHERE ID=?",
+    state,
+    instance_id
+  )
+  .execute(&database)
+  .await
+  .expect("update error");
+  database.close().await;
+}
+
+/// Returns the role of a guild whose members may manage announcements without being admins.
+pub async fn get_manager_role(guild_id: i64) -> Option<i64> {
+  let database = get_database().await;
+  let role = sqlx::query!("SELECT Manager_Role FROM GUILDS WHERE Guild_ID=?", guild_id)
+    .fetch_optional(&database)
+    .await
+    .expect("select error")
+    .and_then(|row| row.Manager_Role);
+  database.close().await;
+  role
+}
+
+pub async fn set_manager_role(guild_id: i64, role_id: Option<i64>) {
+  let database = get_database().await;
+  sqlx::query!(
+    "INSERT INTO GUILDS (Guild_ID, Manager_Role) VALUES (?1, ?2) ON CONFLICT(Guild_ID) DO UPDATE SET Manager_Role=?2",
+    guild_id,
+    role_id
+  )
+  .execute(&database)
+  .await
+  .expect("insert error");
+  database.close().await;
+}
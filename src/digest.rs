@@ -0,0 +1,538 @@
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveTime, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+use serenity::all::{Context, CreateEmbed};
+
+use crate::announcement::{self, Announcement};
+use crate::database::{
+  get_pending, record_announcement, remove_pending, set_digest_last, set_last_error,
+};
+use crate::{Instance, Type, follows};
+
+/// A cron-like schedule: `minute hour day-of-month month day-of-week`.
+///
+/// Every field accepts `*`, `*/step`, numbers, ranges (`1-5`) and lists of those (`0,30`).
+/// Day-of-week starts with 0 for sunday, 7 is sunday as well. Like in cron, a time matches if
+/// either day-of-month or day-of-week matches when both are restricted.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Schedule {
+  minutes: Vec<u32>,
+  hours: Vec<u32>,
+  days: Vec<u32>,
+  months: Vec<u32>,
+  weekdays: Vec<u32>,
+  /// Whether day-of-month and day-of-week are both restricted, i.e. don't start with `*`.
+  either_day: bool,
+}
+
+impl Schedule {
+  pub fn parse(expression: &str) -> Result<Schedule, String> {
+    let fields: Vec<&str> = expression.split_whitespace().collect();
+    if fields.len() != 5 {
+      return Err(
+        "A schedule needs five fields: minute hour day-of-month month day-of-week".to_string(),
+      );
+    }
+    let mut weekdays = parse_field(fields[4], 0, 7)?;
+    if weekdays.contains(&7) {
+      weekdays.push(0);
+    }
+    Ok(Schedule {
+      minutes: parse_field(fields[0], 0, 59)?,
+      hours: parse_field(fields[1], 0, 23)?,
+      days: parse_field(fields[2], 1, 31)?,
+      months: parse_field(fields[3], 1, 12)?,
+      weekdays,
+      either_day: !fields[2].starts_with('*') && !fields[4].starts_with('*'),
+    })
+  }
+
+  pub fn matches(&self, time: DateTime<FixedOffset>) -> bool {
+    let day = self.days.contains(&time.day());
+    let weekday = self
+      .weekdays
+      .contains(&time.weekday().num_days_from_sunday());
+    self.minutes.contains(&time.minute())
+      && self.hours.contains(&time.hour())
+      && self.months.contains(&time.month())
+      && if self.either_day {
+        day || weekday
+      } else {
+        day && weekday
+      }
+  }
+
+  /// Whether the schedule hit a minute in `(last, now]`. Only the last week is checked.
+  pub fn is_due(&self, last: DateTime<Utc>, now: DateTime<Utc>, timezone: Timezone) -> bool {
+    let mut time = last.max(now - Duration::days(7)) + Duration::minutes(1);
+    time = time.with_second(0).unwrap_or(time);
+    while time <= now {
+      if self.matches(timezone.local(time)) {
+        return true;
+      }
+      time += Duration::minutes(1);
+    }
+    false
+  }
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<Vec<u32>, String> {
+  let invalid = || format!("Invalid schedule field \"{}\"", field);
+  let mut values = vec![];
+  for part in field.split(',') {
+    let (range, step) = match part.split_once('/') {
+      Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+      None => (part, 1),
+    };
+    let (start, end) = if range == "*" {
+      (min, max)
+    } else if let Some((start, end)) = range.split_once('-') {
+      (
+        start.parse::<u32>().map_err(|_| invalid())?,
+        end.parse::<u32>().map_err(|_| invalid())?,
+      )
+    } else {
+      let value = range.parse::<u32>().map_err(|_| invalid())?;
+      (value, value)
+    };
+    if start < min || end > max || start > end || step == 0 {
+      return Err(invalid());
+    }
+    values.extend((start..=end).step_by(step as usize));
+  }
+  Ok(values)
+}
+
+/// A timezone, either by name so that daylight saving time is followed, or as a fixed offset.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Timezone {
+  Named(Tz),
+  Fixed(FixedOffset),
+}
+
+impl Default for Timezone {
+  fn default() -> Timezone {
+    Timezone::Named(Tz::UTC)
+  }
+}
+
+impl Timezone {
+  /// The local time of the timezone at `time`.
+  pub fn local(&self, time: DateTime<Utc>) -> DateTime<FixedOffset> {
+    match self {
+      Timezone::Named(timezone) => time.with_timezone(timezone).fixed_offset(),
+      Timezone::Fixed(offset) => time.with_timezone(offset),
+    }
+  }
+}
+
+/// Parses a timezone given as an IANA name like "Europe/Berlin", or as a UTC offset, e.g. "UTC",
+/// "+02:00", "-5" or "+0530".
+pub fn parse_timezone(timezone: &str) -> Option<Timezone> {
+  if let Ok(timezone) = timezone.trim().parse::<Tz>() {
+    return Some(Timezone::Named(timezone));
+  }
+  parse_offset(timezone).map(Timezone::Fixed)
+}
+
+fn parse_offset(timezone: &str) -> Option<FixedOffset> {
+  let timezone = timezone
+    .trim()
+    .trim_start_matches("UTC")
+    .trim_start_matches("GMT");
+  if timezone.is_empty() {
+    return FixedOffset::east_opt(0);
+  }
+  let (sign, offset) = if let Some(offset) = timezone.strip_prefix('+') {
+    (1, offset)
+  } else if let Some(offset) = timezone.strip_prefix('-') {
+    (-1, offset)
+  } else {
+    return None;
+  };
+  if !offset
+    .chars()
+    .all(|char| char.is_ascii_digit() || char == ':')
+  {
+    return None;
+  }
+  let (hours, minutes) = match offset.split_once(':') {
+    Some((hours, minutes)) => (hours, minutes),
+    None if offset.len() == 4 => offset.split_at(2),
+    None => (offset, "0"),
+  };
+  let (hours, minutes) = (hours.parse::<i32>().ok()?, minutes.parse::<i32>().ok()?);
+  // Offsets in use range from -12:00 to +14:00.
+  if hours > 14 || minutes >= 60 {
+    return None;
+  }
+  FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Parses a time of day like "23:00".
+pub fn parse_time(time: &str) -> Option<NaiveTime> {
+  NaiveTime::parse_from_str(time.trim(), "%H:%M").ok()
+}
+
+/// Whether `now` falls into the quiet hours of an instance. Windows may span midnight.
+pub fn is_quiet(server: &Instance, now: DateTime<Utc>) -> bool {
+  let (Some(start), Some(end)) = (
+    server.quiet_start.as_deref().and_then(parse_time),
+    server.quiet_end.as_deref().and_then(parse_time),
+  ) else {
+    return false;
+  };
+  let timezone = server
+    .quiet_timezone
+    .as_deref()
+    .and_then(parse_timezone)
+    .unwrap_or_default();
+  let time = timezone.local(now).time();
+  if start <= end {
+    start <= time && time < end
+  } else {
+    time >= start || time < end
+  }
+}
+
+/// Posts announcements that were held back during quiet hours, or before the digest got
+/// disabled, either one by one or collapsed into one summary.
+pub async fn release(ctx: &Context, server: &Instance) {
+  if server.quiet_collapse {
+    post(ctx, server).await;
+    return;
+  }
+  for (id, announcement) in get_pending(server.id).await {
+    match announcement::deliver(ctx, server, &announcement).await {
+      Ok(_) => {
+        remove_pending(&[id]).await;
+        follows::notify(ctx, server, &announcement).await;
+      },
+      Err(why) => {
+        eprintln!("Error sending message: {why:?}");
+        set_last_error(
+          server.id,
+          Utc::now().timestamp(),
+          &format!("Failed to send an announcement: {why}"),
+        )
+        .await;
+        return;
+      },
+    }
+  }
+}
+
+/// Posts the queued announcements of an instance in digest mode once its schedule is due.
+pub async fn post_if_due(ctx: &Context, server: &Instance) {
+  let Some(schedule) = server
+    .digest_schedule
+    .as_ref()
+    .and_then(|schedule| Schedule::parse(schedule).ok())
+  else {
+    return;
+  };
+  let timezone = server
+    .digest_timezone
+    .as_ref()
+    .and_then(|timezone| parse_timezone(timezone))
+    .unwrap_or_default();
+  let now = Utc::now();
+  let last = server
+    .digest_last
+    .and_then(|last| Utc.timestamp_opt(last, 0).single())
+    .unwrap_or(now);
+  if !schedule.is_due(last, now, timezone) {
+    return;
+  }
+
+  if post(ctx, server).await {
+    set_digest_last(server.id, now.timestamp()).await;
+  }
+}
+
+/// Posts every queued announcement of an instance as one grouped summary.
+///
+/// Returns false if the summary couldn't be posted, in which case the queue is kept.
+pub async fn post(ctx: &Context, server: &Instance) -> bool {
+  let pending = get_pending(server.id).await;
+  if pending.is_empty() {
+    return true;
+  }
+  let announcements: Vec<&Announcement> = pending.iter().map(|(_, a)| a).collect();
+
+  let title = format!("New additions — {}", Utc::now().format("%Y-%m-%d"));
+  let mut first = None;
+  for embeds in summary(&title, &announcements) {
+    match announcement::post(ctx, server, &title, embeds, vec![]).await {
+      Ok(message) => {
+        first.get_or_insert((message.channel_id.get() as i64, message.id.get() as i64));
+      },
+      Err(why) => {
+        eprintln!("Error sending digest: {why:?}");
+        set_last_error(
+          server.id,
+          Utc::now().timestamp(),
+          &format!("Failed to send a digest: {why}"),
+        )
+        .await;
+        return false;
+      },
+    }
+  }
+
+  // Every announcement of the summary points to its first message in the history.
+  for announcement in &announcements {
+    record_announcement(server.id, announcement, first).await;
+  }
+  remove_pending(&pending.iter().map(|(id, _)| *id).collect::<Vec<i64>>()).await;
+  // Followers only hear about new episodes once they have been posted in the channel.
+  for announcement in announcements {
+    follows::notify(ctx, server, announcement).await;
+  }
+  true
+}
+
+/// Groups announcements into Movies / New Series / New Episodes sections and splits them into
+/// messages that fit discord's limits.
+pub fn summary(title: &str, announcements: &[&Announcement]) -> Vec<Vec<CreateEmbed>> {
+  let sections = [
+    ("Movies", vec![Type::Movie]),
+    ("New Series", vec![Type::Series]),
+    (
+      "New Episodes",
+      vec![Type::Season, Type::Episode, Type::Special],
+    ),
+  ];
+
+  let mut embeds: Vec<(usize, CreateEmbed)> = vec![];
+  for (name, kinds) in sections {
+    let items: Vec<&&Announcement> = announcements
+      .iter()
+      .filter(|announcement| kinds.contains(&announcement.kind))
+      .collect();
+    if items.is_empty() {
+      continue;
+    }
+
+    let lines: Vec<String> = items
+      .iter()
+      .map(|announcement| match &announcement.description {
+        Some(description) if !description.is_empty() => {
+          format!("• {} — {}", announcement.title, description)
+        },
+        _ => format!("• {}", announcement.title),
+      })
+      .collect();
+
+    // Embed descriptions are limited to 4096 characters.
+    let mut pages: Vec<String> = vec![String::new()];
+    for line in lines {
+      let line: String = line.chars().take(1000).collect();
+      if pages.last().unwrap().len() + line.len() > 4000 {
+        pages.push(String::new());
+      }
+      let page = pages.last_mut().unwrap();
+      page.push_str(&line);
+      page.push('\n');
+    }
+
+    let count = pages.len();
+    for (i, page) in pages.into_iter().enumerate() {
+      let heading = if count > 1 {
+        format!("{} ({}) — {}/{}", name, items.len(), i + 1, count)
+      } else {
+        format!("{} ({})", name, items.len())
+      };
+      let mut embed = CreateEmbed::new().title(&heading).description(&page);
+      if i == 0 {
+        embed = embed.thumbnail(&items[0].image);
+      }
+      embeds.push((heading.len() + page.len(), embed));
+    }
+  }
+
+  // Messages are limited to 10 embeds and 6000 characters.
+  let mut messages: Vec<Vec<CreateEmbed>> = vec![vec![CreateEmbed::new().title(title)]];
+  let mut size = title.len();
+  for (length, embed) in embeds {
+    if size + length > 5800 || messages.last().unwrap().len() == 10 {
+      messages.push(vec![]);
+      size = 0;
+    }
+    messages.last_mut().unwrap().push(embed);
+    size += length;
+  }
+  messages
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn quiet(start: &str, end: &str, timezone: Option<&str>) -> Instance {
+    Instance {
+      quiet_start: Some(start.to_string()),
+      quiet_end: Some(end.to_string()),
+      quiet_timezone: timezone.map(str::to_string),
+      ..Default::default()
+    }
+  }
+
+  fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 10, 19, hour, minute, 0).unwrap()
+  }
+
+  #[test]
+  fn parse_schedule() {
+    let schedule = Schedule::parse("0,30 */6 * 1-3 5").unwrap();
+    assert_eq!(schedule.minutes, vec![0, 30]);
+    assert_eq!(schedule.hours, vec![0, 6, 12, 18]);
+    assert_eq!(schedule.days, (1..=31).collect::<Vec<u32>>());
+    assert_eq!(schedule.months, vec![1, 2, 3]);
+    assert_eq!(schedule.weekdays, vec![5]);
+    assert_eq!(Schedule::parse("0 9 * * 7").unwrap().weekdays, vec![7, 0]);
+  }
+
+  #[test]
+  fn parse_invalid_schedule() {
+    assert!(Schedule::parse("0 9 * *").is_err());
+    assert!(Schedule::parse("60 9 * * *").is_err());
+    assert!(Schedule::parse("0 9 0 * *").is_err());
+    assert!(Schedule::parse("0 5-3 * * *").is_err());
+    assert!(Schedule::parse("*/0 9 * * *").is_err());
+    assert!(Schedule::parse("a 9 * * *").is_err());
+  }
+
+  #[test]
+  fn schedule_is_due() {
+    let schedule = Schedule::parse("0 9 * * *").unwrap();
+    assert!(schedule.is_due(at(8, 0), at(9, 0), Timezone::default()));
+    assert!(!schedule.is_due(at(9, 0), at(10, 0), Timezone::default()));
+    let berlin = Timezone::Fixed(FixedOffset::east_opt(2 * 3600).unwrap());
+    assert!(schedule.is_due(at(6, 30), at(7, 0), berlin));
+  }
+
+  #[test]
+  fn schedule_matches_either_day() {
+    let utc = FixedOffset::east_opt(0).unwrap();
+    let day = |day: u32| {
+      utc
+        .with_ymd_and_hms(2026, 6, day, 9, 0, 0)
+        .single()
+        .unwrap()
+    };
+    // 2026-06-01 is a monday, 2026-06-08 as well and 2026-06-02 a tuesday.
+    let either = Schedule::parse("0 9 1 * 1").unwrap();
+    assert!(either.matches(day(1)));
+    assert!(either.matches(day(8)));
+    assert!(!either.matches(day(2)));
+    let mondays = Schedule::parse("0 9 * * 1").unwrap();
+    assert!(mondays.matches(day(8)));
+    assert!(!mondays.matches(day(2)));
+    let first = Schedule::parse("0 9 1 * *").unwrap();
+    assert!(first.matches(day(1)));
+    assert!(!first.matches(day(8)));
+  }
+
+  #[test]
+  fn schedule_follows_daylight_saving_time() {
+    let schedule = Schedule::parse("0 9 * * *").unwrap();
+    let berlin = parse_timezone("Europe/Berlin").unwrap();
+    // 09:00 in Berlin is 07:00 UTC in summer and 08:00 UTC in winter.
+    let summer = Utc.with_ymd_and_hms(2026, 7, 1, 7, 0, 0).unwrap();
+    let winter = Utc.with_ymd_and_hms(2026, 12, 1, 8, 0, 0).unwrap();
+    assert!(schedule.is_due(summer - Duration::minutes(1), summer, berlin));
+    assert!(schedule.is_due(winter - Duration::minutes(1), winter, berlin));
+    assert!(!schedule.is_due(
+      winter - Duration::hours(1),
+      winter - Duration::minutes(1),
+      berlin
+    ));
+  }
+
+  #[test]
+  fn parse_named_timezones() {
+    assert_eq!(parse_timezone("UTC"), Some(Timezone::Named(Tz::UTC)));
+    assert_eq!(
+      parse_timezone(" Europe/Berlin "),
+      Some(Timezone::Named(Tz::Europe__Berlin))
+    );
+    assert_eq!(
+      parse_timezone("America/New_York"),
+      Some(Timezone::Named(Tz::America__New_York))
+    );
+    assert_eq!(parse_timezone("Europe/Nowhere"), None);
+  }
+
+  #[test]
+  fn parse_offsets() {
+    let offset = |seconds| FixedOffset::east_opt(seconds).map(Timezone::Fixed);
+    assert_eq!(parse_timezone("UTC+0"), offset(0));
+    assert_eq!(parse_timezone("+02:00"), offset(7200));
+    assert_eq!(parse_timezone("UTC-5"), offset(-18000));
+    assert_eq!(parse_timezone("GMT+0530"), offset(19800));
+    assert_eq!(parse_timezone("+14"), offset(50400));
+  }
+
+  #[test]
+  fn parse_invalid_offsets() {
+    assert_eq!(parse_timezone("Berlin"), None);
+    assert_eq!(parse_timezone("2"), None);
+    assert_eq!(parse_timezone("+"), None);
+    assert_eq!(parse_timezone("+15"), None);
+    assert_eq!(parse_timezone("+02:60"), None);
+    assert_eq!(parse_timezone("+-2"), None);
+    assert_eq!(parse_timezone("+99999999999"), None);
+    assert_eq!(parse_timezone("é"), None);
+    assert_eq!(parse_timezone("+é1"), None);
+    assert_eq!(parse_timezone("+1é"), None);
+  }
+
+  #[test]
+  fn quiet_hours() {
+    let day = quiet("09:00", "17:00", None);
+    assert!(is_quiet(&day, at(9, 0)));
+    assert!(is_quiet(&day, at(16, 59)));
+    assert!(!is_quiet(&day, at(17, 0)));
+    assert!(!is_quiet(&day, at(8, 59)));
+  }
+
+  #[test]
+  fn quiet_hours_over_midnight() {
+    let night = quiet("23:00", "07:00", None);
+    assert!(is_quiet(&night, at(23, 30)));
+    assert!(is_quiet(&night, at(3, 0)));
+    assert!(!is_quiet(&night, at(7, 0)));
+    assert!(!is_quiet(&night, at(12, 0)));
+  }
+
+  #[test]
+  fn quiet_hours_in_timezone() {
+    let night = quiet("23:00", "07:00", Some("+02:00"));
+    assert!(is_quiet(&night, at(21, 0)));
+    assert!(!is_quiet(&night, at(5, 0)));
+  }
+
+  #[test]
+  fn quiet_hours_follow_daylight_saving_time() {
+    let night = quiet("23:00", "07:00", Some("Europe/Berlin"));
+    // 05:30 UTC is 07:30 in Berlin in summer, but 06:30 in winter.
+    assert!(!is_quiet(
+      &night,
+      Utc.with_ymd_and_hms(2026, 7, 1, 5, 30, 0).unwrap()
+    ));
+    assert!(is_quiet(
+      &night,
+      Utc.with_ymd_and_hms(2026, 12, 1, 5, 30, 0).unwrap()
+    ));
+    assert!(is_quiet(
+      &night,
+      Utc.with_ymd_and_hms(2026, 7, 1, 4, 30, 0).unwrap()
+    ));
+  }
+
+  #[test]
+  fn quiet_hours_not_set() {
+    assert!(!is_quiet(&Instance::default(), at(3, 0)));
+    assert!(!is_quiet(&quiet("late", "07:00", None), at(3, 0)));
+  }
+}
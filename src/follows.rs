@@ -0,0 +1,76 @@
+use serenity::all::{Context, CreateMessage, GuildId, RoleId, UserId};
+
+use crate::announcement::{Announcement, series_of};
+use crate::database::{get_followers, get_mentions, take_dm_quota};
+use crate::{Instance, Type};
+
+/// Direct messages a member receives per hour at most. Further notifications are dropped.
+const DM_LIMIT_PER_HOUR: i64 = 10;
+
+/// Sends the announcement of new episodes to the members following the series.
+pub async fn notify(ctx: &Context, server: &Instance, announcement: &Announcement) {
+  if matches!(announcement.kind, Type::Movie | Type::Series) {
+    return;
+  }
+  let Some((series_id, series_name)) = series_of(&announcement.item) else {
+    return;
+  };
+
+  let now = chrono::offset::Utc::now().timestamp();
+  for user_id in get_followers(server.id, &series_id).await {
+    if !take_dm_quota(user_id, now, DM_LIMIT_PER_HOUR).await {
+      println!("Skipping direct message to {user_id}: hourly limit reached");
+      continue;
+    }
+    let message = CreateMessage::new()
+      .content(format!(
+        "New on **{series_name}**, which you follow. Use `/unfollow` to stop following it or `/following dms:False` to stop all of these messages."
+      ))
+      .embeds(announcement.embeds());
+    if let Err(why) = UserId::new(user_id as u64)
+      .direct_message(ctx, message)
+      .await
+    {
+      eprintln!("Error sending direct message to {user_id}: {why:?}");
+    }
+  }
+}
+
+/// Gives a follower the roles mentioned for new episodes of the series, see `/mentions series`,
+/// or takes them away again. Returns the roles discord refused to change.
+pub async fn sync_roles(
+  ctx: &Context,
+  server: &Instance,
+  series_id: &str,
+  user_id: i64,
+  follows: bool,
+) -> Vec<RoleId> {
+  let Some(guild_id) = server.guild_id else {
+    return vec![];
+  };
+  let guild_id = GuildId::new(guild_id as u64);
+  let user_id = UserId::new(user_id as u64);
+  let mut failed = vec![];
+  for mention in get_mentions(server.id).await {
+    if mention.kind != "series" || mention.value != series_id {
+      continue;
+    }
+    let role_id = RoleId::new(mention.role_id as u64);
+    let result = if follows {
+      ctx
+        .http
+        .add_member_role(guild_id, user_id, role_id, Some("Follows the series"))
+        .await
+    } else {
+      ctx
+        .http
+        .remove_member_role(guild_id, user_id, role_id, Some("Unfollowed the series"))
+        .await
+    };
+    if let Err(why) = result {
+      eprintln!("Failed to change the follower role {role_id} of {user_id}: {why}");
+      failed.push(role_id);
+    }
+  }
+  failed
+}
@@ -1,43 +1,158 @@
 #![allow(non_snake_case)]
 use config::{Config, File};
 use regex::Regex;
+use serde::de::DeserializeOwned;
 use serde_derive::{Deserialize, Serialize};
-use serenity::all::{
-  ActivityData, CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage,
-  CreateMessage,
-};
+use serenity::all::{ActivityData, CreateInteractionResponse, CreateInteractionResponseMessage};
 use serenity::async_trait;
-use serenity::model::id::{ChannelId, GuildId};
+use serenity::model::id::GuildId;
 use serenity::model::prelude::*;
 use serenity::prelude::*;
+use std::collections::HashSet;
 use std::env;
+use std::fmt;
 use std::path::Path;
 use std::process::exit;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
+mod announcement;
 mod commands;
+mod crypto;
 mod database;
+mod digest;
+mod follows;
 use database::*;
 
 #[derive(Deserialize)]
 struct ConfigFile {
   discord_token: String,
+  /// Base64 encoded key the stored API keys are encrypted with.
+  /// `JELLYCORD_ENCRYPTION_KEY` takes precedence.
+  encryption_key: Option<String>,
+  /// More new items than this in a single check are held back until an admin confirms them.
+  /// 0 disables the limit.
+  #[serde(default = "default_max_announcements")]
+  max_announcements_per_cycle: usize,
+  /// Registers the commands only in this guild, where changes show up instantly. Meant for
+  /// testing, global commands are left alone.
+  dev_guild: Option<u64>,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+fn default_max_announcements() -> usize {
+  100
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
 pub struct Instance {
+  pub id: i64,
   pub active_channel: i64,
   pub channel_id: i64,
   pub domain: String,
   pub token: String,
   pub user_id: String,
+  pub webhook_url: Option<String>,
+  pub webhook_username: Option<String>,
+  pub webhook_avatar: Option<String>,
+  /// 0 = all libraries, 1 = only the listed libraries, 2 = all but the listed libraries
+  pub library_mode: i64,
+  /// Cron-like schedule of the digest. Announcements are posted right away if not set.
+  pub digest_schedule: Option<String>,
+  /// UTC offset the digest schedule is evaluated in.
+  pub digest_timezone: Option<String>,
+  /// Timestamp of the last digest.
+  pub digest_last: Option<i64>,
+  /// Start of the quiet hours ("HH:MM"), during which announcements are held back.
+  pub quiet_start: Option<String>,
+  pub quiet_end: Option<String>,
+  pub quiet_timezone: Option<String>,
+  /// Whether held back announcements are released as one summary.
+  pub quiet_collapse: bool,
+  /// Guild of the announcement channel.
+  pub guild_id: Option<i64>,
+  /// Name of the jellyfin user, if known.
+  pub user_name: Option<String>,
+  /// Timestamp of the last successful poll.
+  pub last_poll: Option<i64>,
+  pub last_error: Option<String>,
+  pub last_error_at: Option<i64>,
+  /// Timestamp after which a paused instance resumes on its own.
+  pub pause_until: Option<i64>,
+  pub pause_reason: Option<String>,
+  /// Whether items arriving during the pause are queued instead of only marked as seen.
+  pub pause_queue: bool,
+  /// 0 = announcing, 1 = too many new items, waiting for an admin, 2 = confirmed to announce them
+  pub valve_state: i64,
+}
+
+/// Sends announcements that match `kind` and `value` to another channel.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Route {
+  pub id: i64,
+  /// One of "type", "library", "genre" or "tag".
+  pub kind: String,
+  pub value: String,
+  pub channel_id: i64,
+}
+
+/// Prevents announcements matching `kind` and `value` from being posted.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Filter {
+  pub id: i64,
+  /// One of "rating", "tag", "genre", "score" or "audio".
+  pub kind: String,
+  pub value: String,
+}
+
+/// Pings a role for announcements matching `kind` and `value`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Mention {
+  pub id: i64,
+  /// One of "all", "genre", "library" or "series".
+  pub kind: String,
+  pub value: String,
+  pub role_id: i64,
+}
+
+/// A series a member gets direct messages about.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Follow {
+  pub id: i64,
+  pub user_id: i64,
+  pub instance_id: i64,
+  pub series_id: String,
+  pub series_name: String,
+}
+
+/// A posted, or failed, announcement as recorded for `/history`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct HistoryEntry {
+  pub id: i64,
+  pub instance_id: i64,
+  pub title: String,
+  pub kind: String,
+  pub channel_id: Option<i64>,
+  pub message_id: Option<i64>,
+  pub created: i64,
+  /// Either "posted", "failed" or "deleted".
+  pub status: String,
+  /// The serialized announcement, used to post it again.
+  pub announcement: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 struct UserList {
   Name: String,
   Id: String,
+  Policy: Option<UserPolicy>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct UserPolicy {
+  #[serde(default)]
+  IsAdministrator: bool,
+  #[serde(default)]
+  IsDisabled: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -45,6 +160,18 @@ struct MediaResponse {
   Items: Vec<Item>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct ViewList {
+  Items: Vec<View>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct View {
+  Name: String,
+  Id: String,
+  CollectionType: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 enum Type {
   Movie,
@@ -68,11 +195,19 @@ struct Item {
   SeasonId: Option<String>,
   MediaStreams: Option<Vec<MediaStream>>,
   CommunityRating: Option<f64>,
+  OfficialRating: Option<String>,
   RunTimeTicks: Option<u64>,
   PremiereDate: Option<String>,
   pub ProductionYear: Option<u32>,
   Status: Option<String>,
   EndDate: Option<String>,
+  Genres: Option<Vec<String>>,
+  Tags: Option<Vec<String>>,
+  /// Only requested when browsing the latest items.
+  DateCreated: Option<String>,
+  /// The library (user view) the item was found in. Not part of the response.
+  #[serde(default)]
+  LibraryId: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -80,6 +215,7 @@ struct MediaStream {
   Type: String,
   Language: Option<String>,
   Height: Option<u32>,
+  Width: Option<u32>,
   IsInterlaced: bool,
 }
 
@@ -100,20 +236,20 @@ impl LibraryTools for Vec<Vec<Item>> {
   }
 }
 
-impl ToString for Type {
-  fn to_string(&self) -> String {
+impl fmt::Display for Type {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     match self {
-      Self::Movie => String::from("Movie"),
-      Self::Episode => String::from("Episode"),
-      Self::Season => String::from("Season"),
-      Self::Series => String::from("Series"),
-      Self::Special => String::from("Special"),
+      Self::Movie => write!(f, "Movie"),
+      Self::Episode => write!(f, "Episode"),
+      Self::Season => write!(f, "Season"),
+      Self::Series => write!(f, "Series"),
+      Self::Special => write!(f, "Special"),
     }
   }
 }
 
-impl ToString for Item {
-  fn to_string(&self) -> String {
+impl fmt::Display for Item {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     let time = if let (Some(start), Some(end)) = (self.PremiereDate.clone(), self.EndDate.clone()) {
       if start[0..4] == end[0..4] {
         format!("({})", &start[0..4])
@@ -142,7 +278,7 @@ impl ToString for Item {
       name = re.replace_all(&name, "").to_string();
     }
 
-    match self.Type {
+    let title = match self.Type {
       Type::Movie | Type::Series => {
         format!("{} {}", name, time)
       },
@@ -173,51 +309,144 @@ impl ToString for Item {
         },
       },
       _ => format!("{} {} (unknown media type)", self.Name, time),
-    }
+    };
+    write!(f, "{}", title)
   }
 }
 
 struct Handler {
   is_loop_running: AtomicBool,
+  max_announcements_per_cycle: usize,
+  dev_guild: Option<GuildId>,
 }
 
 #[async_trait]
 impl EventHandler for Handler {
   async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
     if let Interaction::Command(command) = interaction {
-      let content = match command.data.name.as_str() {
-        "help" => commands::help::run(&command.data.options).await,
-        "init" => commands::init::run(&command.data.options).await,
-        "reset" => commands::reset::run(&command.data.options).await,
-        "pause" => commands::pause::run(&command.data.options).await,
-        "ping" => commands::ping::run(&command.data.options).await,
-        _ => "Not implemented >~< - (Contact: @DepriSheep)".to_string(),
+      let access = commands::access(&command.data.name);
+      if !commands::is_allowed(command.member.as_deref(), access).await {
+        let content = match access {
+          commands::Access::Admin => "Only administrators can use this command.",
+          _ => "Only managers can use this command, see `/managers`.",
+        };
+        let builder = CreateInteractionResponse::Message(
+          CreateInteractionResponseMessage::new()
+            .content(content)
+            .ephemeral(true),
+        );
+        if let Err(why) = command.create_response(&ctx.http, builder).await {
+          println!("Cannot respond to slash command: {}", why);
+        }
+        return;
+      }
+      let options = &command.data.options;
+      let guild_id = command.guild_id;
+      let user_id = command.user.id.get() as i64;
+      // Replies can contain details of the server, so they're only shown to the user.
+      let reply = |data: CreateInteractionResponseMessage| {
+        CreateInteractionResponse::Message(data.ephemeral(true))
+      };
+      let text = |content: String| reply(CreateInteractionResponseMessage::new().content(content));
+      let builder = match command.data.name.as_str() {
+        // Deferred commands respond on their own.
+        "resync" => return commands::resync::run(&ctx, &command).await,
+        "preview" => return commands::preview::run(&ctx, &command).await,
+        "stats" => return commands::stats::run(&ctx, &command).await,
+        "libraries" => return commands::libraries::run(&ctx, &command).await,
+        "init" => commands::init::run(&ctx, &command).await,
+        "edit" => commands::edit::run(&ctx, &command).await,
+        "status" => reply(commands::status::run(guild_id, options).await),
+        "list" => reply(commands::list::run(guild_id, options).await),
+        "reset" => reply(commands::reset::run(options).await),
+        "history" => reply(commands::history::run(guild_id, options).await),
+        "latest" => reply(commands::latest::run(guild_id, options).await),
+        "search" => reply(commands::search::run(guild_id, options).await),
+        "help" => text(commands::help::run(options).await),
+        "pause" => text(commands::pause::run(options).await),
+        "resume" => text(commands::resume::run(options).await),
+        "ping" => text(commands::ping::run(options).await),
+        "route" => text(commands::route::run(options).await),
+        "filter" => text(commands::filter::run(options).await),
+        "digest" => text(commands::digest::run(options).await),
+        "quiet" => text(commands::quiet::run(options).await),
+        "mentions" => text(commands::mentions::run(&ctx, guild_id, options).await),
+        "follow" => text(commands::follow::run(&ctx, guild_id, user_id, options).await),
+        "following" => text(commands::following::run(user_id, options).await),
+        "unfollow" => text(commands::unfollow::run(&ctx, user_id, options).await),
+        "popular" => text(commands::popular::run(guild_id, options).await),
+        "managers" => text(commands::managers::run(guild_id, options).await),
+        _ => text("Not implemented >~< - (Contact: @DepriSheep)".to_string()),
       };
 
-      let data = CreateInteractionResponseMessage::new().content(content);
-      let builder = CreateInteractionResponse::Message(data);
       if let Err(why) = command.create_response(&ctx.http, builder).await {
         println!("Cannot respond to slash command: {}", why);
       }
+    } else if let Interaction::Autocomplete(autocomplete) = interaction {
+      let Some(focused) = autocomplete.data.autocomplete() else {
+        return;
+      };
+      let response = match autocomplete.data.name.as_str() {
+        "follow" => commands::follow::autocomplete(autocomplete.guild_id, focused.value).await,
+        "unfollow" => {
+          commands::unfollow::autocomplete(autocomplete.user.id.get() as i64, focused.value).await
+        },
+        "preview" => commands::preview::autocomplete(autocomplete.guild_id, focused.value).await,
+        "search" => {
+          commands::search::autocomplete(
+            autocomplete.guild_id,
+            &autocomplete.data.options,
+            focused.value,
+          )
+          .await
+        },
+        _ => return,
+      };
+      let builder = CreateInteractionResponse::Autocomplete(response);
+      if let Err(why) = autocomplete.create_response(&ctx.http, builder).await {
+        println!("Cannot respond to autocomplete: {}", why);
+      }
+    } else if let Interaction::Modal(modal) = interaction {
+      match modal.data.custom_id.split(':').next() {
+        Some("init") => commands::init::submit(&ctx, &modal).await,
+        Some("edit") => commands::edit::submit(&ctx, &modal).await,
+        _ => {},
+      }
+    } else if let Interaction::Component(component) = interaction {
+      if component.data.custom_id.starts_with("init:") {
+        commands::init::select(&ctx, &component).await;
+        return;
+      }
+      let builder = match component.data.custom_id.split(':').next() {
+        Some("libraries") => {
+          commands::libraries::select(&ctx, &component).await;
+          return;
+        },
+        Some("interest") => commands::popular::interest(&component).await,
+        Some("list") => commands::list::turn(&component).await,
+        Some("reset") => commands::reset::confirm(&component).await,
+        Some("latest") => commands::latest::turn(&component).await,
+        Some("search") => commands::search::turn(&component).await,
+        Some("history") => {
+          commands::history::interact(&ctx, &component).await;
+          return;
+        },
+        Some("valve") => {
+          commands::resync::valve(&ctx, &component).await;
+          return;
+        },
+        _ => return,
+      };
+      if let Err(why) = component.create_response(&ctx.http, builder).await {
+        println!("Cannot respond to component: {}", why);
+      }
     }
   }
 
   async fn ready(&self, ctx: Context, ready: Ready) {
-    Command::create_global_command(&ctx.http, commands::help::register())
-      .await
-      .unwrap();
-    Command::create_global_command(&ctx.http, commands::init::register())
-      .await
-      .unwrap();
-    Command::create_global_command(&ctx.http, commands::pause::register())
-      .await
-      .unwrap();
-    Command::create_global_command(&ctx.http, commands::reset::register())
-      .await
-      .unwrap();
-    Command::create_global_command(&ctx.http, commands::ping::register())
-      .await
-      .unwrap();
+    if let Err(why) = commands::register_commands(&ctx, self.dev_guild).await {
+      eprintln!("Failed to register the commands: {why:?}");
+    }
 
     println!("{} is connected!", ready.user.name);
     ctx.set_activity(Some(ActivityData::watching("the internet.")));
@@ -225,16 +454,57 @@ impl EventHandler for Handler {
 
   async fn cache_ready(&self, ctx: Context, _guilds: Vec<GuildId>) {
     println!("Cache built successfully!");
+    // Instances created before guilds were stored don't know theirs yet.
+    for server in get_instances().await {
+      if server.guild_id.is_some() {
+        continue;
+      }
+      if let Ok(Channel::Guild(channel)) = ChannelId::new(server.channel_id as u64)
+        .to_channel(&ctx)
+        .await
+      {
+        set_guild(server.id, channel.guild_id.get() as i64).await;
+      }
+    }
     if !self.is_loop_running.load(Ordering::Relaxed) {
+      let max_announcements = self.max_announcements_per_cycle;
       tokio::spawn(async move {
         'main: loop {
-          let front_db = get_front_database().await;
-          for server in front_db {
-            let timed_response_obj = get_serialized_page(format!(
-              "{}/Users/{}/Items?api_key={}&Recursive=true&IncludeItemTypes=Movie,Series,Episode,Season,Special&Fields=MediaStreams&collapseBoxSetItems=False",
-              server.domain, server.user_id, server.token
-            )).await;
+          purge_archive(
+            chrono::offset::Utc::now().timestamp() - commands::reset::ARCHIVE_DAYS * 86400,
+          )
+          .await;
+          let front_db = get_instances().await;
+          for mut server in front_db {
+            if server.active_channel == 0
+              && server
+                .pause_until
+                .is_some_and(|until| until <= chrono::offset::Utc::now().timestamp())
+            {
+              println!("Resuming instance {} after its pause", server.id);
+              resume_instance(server.id).await;
+              server.active_channel = 1;
+            }
+            // Paused instances are still polled, so that items arriving during the pause can be
+            // marked as seen or queued.
+            let paused = server.active_channel == 0;
+            let quiet = digest::is_quiet(&server, chrono::offset::Utc::now());
+            if !paused {
+              if server.digest_schedule.is_some() {
+                digest::post_if_due(&ctx, &server).await;
+              } else if !quiet {
+                digest::release(&ctx, &server).await;
+              }
+            }
+
+            let timed_response_obj = get_serialized_library(&server).await;
             if let Ok(serialized_server) = timed_response_obj {
+              set_last_poll(server.id, chrono::offset::Utc::now().timestamp()).await;
+              if server.user_name.is_none()
+                && let Ok(user) = get_user(&server).await
+              {
+                set_user_name(server.id, &user.Name).await;
+              }
               let lib = get_library_by_user(server.clone().user_id).await;
 
               // Fill the library if it's empty
@@ -331,667 +601,81 @@ impl EventHandler for Handler {
 
               new_items.reverse();
 
-              for itemlist in new_items.iter_mut() {
-                if itemlist.len() == 1 {
-                  let item = itemlist[0].clone();
-                  if let Some(streams) = item.MediaStreams.clone() {
-                    if streams.is_empty() {
-                      continue;
-                    }
-                  }
-
-                  if item.Type == Type::Episode
-                    || item.Type == Type::Special
-                    || item.Type == Type::Movie
-                  {
-                    let name = item.to_string();
-                    let image = format!(
-                      "{}/Items/{}/Images/Primary?Quality=100",
-                      server.domain,
-                      item.clone().SeasonId.unwrap_or(item.clone().Id)
-                    );
-                    let (resolution, a_languages, s_languages) = if item.MediaStreams.is_some() {
-                      let mut height: String = String::new();
-                      let mut a_languages: String = String::new();
-                      let mut s_languages: String = String::new();
-                      let mut scan_type: char = 'p';
-                      for x in item.MediaStreams.unwrap() {
-                        if x.Type == "Video" {
-                          height = x.Height.unwrap().to_string();
-                          if x.IsInterlaced {
-                            scan_type = 'i';
-                          }
-                        } else if x.Type == "Audio" {
-                          a_languages.push_str(&(x.Language.unwrap_or("?".to_string()) + ", "))
-                        } else if x.Type == "Subtitle" {
-                          s_languages.push_str(&(x.Language.unwrap_or("?".to_string()) + ", "))
-                        }
-                      }
-                      if height.is_empty() {
-                        height = "?".to_string()
-                      }
-                      if a_languages.is_empty() {
-                        a_languages = "?".to_string()
-                      } else if a_languages != *"?" {
-                        a_languages = a_languages.strip_suffix(", ").unwrap().to_string();
-                      }
-                      if s_languages != *"?" && !s_languages.is_empty() {
-                        s_languages = s_languages.strip_suffix(", ").unwrap().to_string();
-                      } else {
-                        s_languages = String::new()
-                      }
-                      height.push(scan_type);
-                      (height, a_languages, s_languages)
-                    } else {
-                      ("?".to_string(), "?".to_string(), String::new())
-                    };
-                    let runtime: String = if item.RunTimeTicks.is_some() {
-                      let time = (item.RunTimeTicks.unwrap() as f64) / 10000000.0;
-                      let formated: String = if time > 60.0 {
-                        if (time / 60.0) > 60.0 {
-                          format!(
-                            "{:02}:{:02}:{:02}",
-                            ((time / 60.0) / 60.0).trunc(),
-                            ((((time / 60.0) / 60.0) - ((time / 60.0) / 60.).trunc()) * 60.0)
-                              .trunc(),
-                            (((time / 60.0) - (time / 60.0).trunc()) * 60.0).trunc()
-                          )
-                        } else {
-                          format!(
-                            "00:{:02}:{:02}",
-                            (time / 60.0).trunc(),
-                            (((time / 60.0) - (time / 60.0).trunc()) * 60.0).trunc()
-                          )
-                        }
-                      } else {
-                        format!("00:00:{time:02}")
-                      };
-                      formated
-                    } else {
-                      "?".to_string()
-                    };
-
-                    let mut fields = Vec::new();
-                    fields.push((
-                      ":star: — Rating".to_string(),
-                      if let Some(rating) = item.CommunityRating {
-                        format!("{:.2}", rating)
-                      } else {
-                        "?".to_string()
-                      },
-                      true,
-                    ));
-                    fields.push((
-                      ":film_frames: — Runtime".to_string(),
-                      runtime.to_string(),
-                      true,
-                    ));
-                    fields.push((
-                      ":frame_photo: — Resolution".to_string(),
-                      resolution.to_string(),
-                      true,
-                    ));
-                    fields.push((
-                      ":loud_sound: — Languages".to_string(),
-                      a_languages.to_string(),
-                      false,
-                    ));
-
-                    if !s_languages.is_empty() {
-                      fields.push((
-                        ":notepad_spiral: — Languages".to_string(),
-                        s_languages.to_string(),
-                        false,
-                      ));
-                    }
-
-                    let mut embed = CreateEmbed::default();
-                    for (name, value, inline) in &fields {
-                      embed = embed.field(name.clone(), value.clone(), *inline);
-                    }
-
-                    let res = ChannelId::new(server.channel_id as u64)
-                      .send_message(
-                        &ctx,
-                        CreateMessage::new()
-                          .add_embed(CreateEmbed::new().title(name).image(image))
-                          .add_embed(embed),
-                      )
-                      .await;
-
-                    if let Err(why) = res {
-                      eprintln!("Error sending message: {why:?}");
-                    } else {
-                      let database = sqlx::sqlite::SqlitePoolOptions::new()
-                        .max_connections(5)
-                        .connect_with(
-                          sqlx::sqlite::SqliteConnectOptions::new()
-                            .filename("jellycord.sqlite")
-                            .create_if_missing(true),
-                        )
-                        .await
-                        .expect("Couldn't connect to database");
-                      sqlx::query(
-                        format!(
-                          "INSERT INTO LIBRARY ({:?}) VALUES (\"{}\")",
-                          &server.user_id, &item.Id
-                        )
-                        .as_str(),
-                      )
-                      .execute(&database)
-                      .await
-                      .expect("insert error");
-                    }
-                  } else if item.Type == Type::Season || item.Type == Type::Series {
-                    let mut ids: Vec<String> = vec![item.Id.clone()];
-                    let seasons = if item.Type == Type::Series {
-                      let mut temp = vec![];
-                      for season in pre_season_items.clone() {
-                        if season.SeriesId.clone().unwrap() == item.Id {
-                          ids.push(season.Id.clone());
-                          temp.push(season);
-                        }
-                      }
-                      temp
-                    } else {
-                      vec![item.clone()]
-                    };
-
-                    let mut desc = String::new();
-                    let mut a_languages: Vec<String> = vec![];
-                    let mut s_languages: Vec<String> = vec![];
-                    let mut v_resolutions: Vec<String> = vec![];
-                    let mut ratings: Vec<f64> = vec![];
-                    let mut total_runtime: u64 = 0;
-
-                    let mut current_start = -1;
-                    for season in seasons {
-                      for (i, episode) in pre_episode_items.clone().iter().enumerate() {
-                        if episode.SeasonId.clone().unwrap() != season.Id {
-                          continue;
-                        }
-
-                        ids.push(episode.Id.clone());
-
-                        if let Some(mediastreams) = &episode.MediaStreams {
-                          for x in mediastreams {
-                            if x.Type == "Video" {
-                              let resolution: String;
-                              let scan_type: char;
-
-                              if x.IsInterlaced {
-                                scan_type = 'i';
-                              } else {
-                                scan_type = 'p';
-                              }
-
-                              if let Some(height) = x.Height {
-                                resolution = height.to_string() + &scan_type.to_string();
-                              } else {
-                                resolution = String::from("?") + &scan_type.to_string();
-                              }
-
-                              if !v_resolutions.contains(&resolution) {
-                                v_resolutions.push(resolution);
-                              }
-                            } else if x.Type == "Audio" {
-                              let lang = x.Language.clone().unwrap_or("?".to_string());
-                              if !a_languages.contains(&lang) {
-                                a_languages.push(lang);
-                              }
-                            } else if x.Type == "Subtitle" {
-                              let lang = x.Language.clone().unwrap_or("?".to_string());
-                              if !s_languages.contains(&lang) {
-                                s_languages.push(lang);
-                              }
-                            }
-                          }
-                        }
-
-                        if let Some(runtime) = episode.RunTimeTicks {
-                          total_runtime += runtime;
-                        }
-
-                        if let Some(rating) = episode.CommunityRating {
-                          ratings.push(rating);
-                        }
-
-                        let index_start = episode.IndexNumber.unwrap() as i32;
-                        let index_end = if let Some(end) = episode.IndexNumberEnd {
-                          end as i32
-                        } else {
-                          index_start
-                        };
-                        let item_name_full = match episode.IndexNumberEnd {
-                          Some(indexend) => {
-                            format!(
-                              "S{:02}E{:02}-{:02}",
-                              episode.ParentIndexNumber.unwrap_or(0),
-                              episode.IndexNumber.unwrap_or(0),
-                              indexend
-                            )
-                          },
-                          None => {
-                            format!(
-                              "S{:02}E{:02}",
-                              episode.ParentIndexNumber.unwrap_or(0),
-                              episode.IndexNumber.unwrap_or(0)
-                            )
-                          },
-                        };
-                        let item_name_end = match episode.IndexNumberEnd {
-                          Some(indexend) => {
-                            format!(
-                              "S{:02}E{:02}",
-                              episode.ParentIndexNumber.unwrap_or(0),
-                              indexend
-                            )
-                          },
-                          None => {
-                            format!(
-                              "S{:02}E{:02}",
-                              episode.ParentIndexNumber.unwrap_or(0),
-                              episode.IndexNumber.unwrap_or(0)
-                            )
-                          },
-                        };
-                        let item_name_start = format!(
-                          "S{:02}E{:02}",
-                          episode.ParentIndexNumber.unwrap_or(0),
-                          episode.IndexNumber.unwrap_or(0)
-                        );
-
-                        if pre_episode_items.len() - 1 == i {
-                          if current_start == -1 {
-                            desc.push_str(&format!("{}", item_name_full));
-                          } else {
-                            desc.push_str(&format!("-{}", item_name_end));
-                          }
-                        } else if i == 0 || current_start == -1 {
-                          if pre_episode_items[i + 1].IndexNumber.unwrap() as i32 != index_end + 1 {
-                            desc.push_str(&format!("{}, ", item_name_full));
-                            current_start = -1;
-                            continue;
-                          } else {
-                            desc.push_str(&item_name_start);
-                          }
-                        } else if pre_episode_items[i + 1].IndexNumber.unwrap() as i32
-                          != index_end + 1
-                        {
-                          desc.push_str(&format!("-{}, ", item_name_end));
-                          current_start = -1;
-                          continue;
-                        }
-                        current_start = index_start;
-                      }
-                    }
-
-                    let image = format!(
-                      "{}/Items/{}/Images/Primary?api_key={}&Quality=100",
-                      server.domain,
-                      item.clone().SeasonId.unwrap_or(item.clone().Id),
-                      server.token
-                    );
-                    let name = item.to_string();
-
-                    let time = (total_runtime as f64) / 10000000.0;
-                    let formatted_runtime: String = if time > 60.0 {
-                      if (time / 60.0) > 60.0 {
-                        format!(
-                          "{:02}:{:02}:{:02}",
-                          ((time / 60.0) / 60.0).trunc(),
-                          ((((time / 60.0) / 60.0) - ((time / 60.0) / 60.).trunc()) * 60.0).trunc(),
-                          (((time / 60.0) - (time / 60.0).trunc()) * 60.0).trunc()
-                        )
-                      } else {
-                        format!(
-                          "00:{:02}:{:02}",
-                          (time / 60.0).trunc(),
-                          (((time / 60.0) - (time / 60.0).trunc()) * 60.0).trunc()
-                        )
-                      }
-                    } else {
-                      format!("00:00:{time:02}")
-                    };
-
-                    let mut fields = Vec::new();
-                    fields.push((
-                      ":star: — Rating".to_string(),
-                      format!("{:.2}", ratings.iter().sum::<f64>() / ratings.len() as f64),
-                      true,
-                    ));
-                    fields.push((
-                      ":film_frames: — Runtime".to_string(),
-                      formatted_runtime.to_string(),
-                      true,
-                    ));
-                    fields.push((
-                      ":frame_photo: — Resolution".to_string(),
-                      v_resolutions.join(", "),
-                      true,
-                    ));
-                    fields.push((
-                      ":loud_sound: — Languages".to_string(),
-                      // 205 is the exact max amount of langs to show (they should all be 3 chars long)
-                      a_languages
-                        .iter()
-                        .take(205)
-                        .map(|s| s.as_str())
-                        .collect::<Vec<&str>>()
-                        .join(", "),
-                      false,
-                    ));
-
-                    if !s_languages.is_empty() {
-                      fields.push((
-                        ":notepad_spiral: — Languages".to_string(),
-                        s_languages
-                          .iter()
-                          .take(205)
-                          .map(|s| s.as_str())
-                          .collect::<Vec<&str>>()
-                          .join(", "),
-                        false,
-                      ));
-                    }
-
-                    let mut embed = CreateEmbed::default();
-                    for (name, value, inline) in &fields {
-                      embed = embed.field(name.clone(), value.clone(), *inline);
-                    }
-
-                    let res = ChannelId::new(server.channel_id as u64)
-                      .send_message(
-                        &ctx,
-                        CreateMessage::new()
-                          .add_embed(
-                            CreateEmbed::new()
-                              .title(name)
-                              .image(image)
-                              .description(desc),
-                          )
-                          .add_embed(embed),
-                      )
-                      .await;
-
-                    if let Err(why) = res {
-                      eprintln!("Error sending message: {why:?}");
-                    } else {
-                      for x in ids {
-                        let database = sqlx::sqlite::SqlitePoolOptions::new()
-                          .max_connections(5)
-                          .connect_with(
-                            sqlx::sqlite::SqliteConnectOptions::new()
-                              .filename("jellycord.sqlite")
-                              .create_if_missing(true),
-                          )
-                          .await
-                          .expect("Couldn't connect to database");
-                        sqlx::query(
-                          format!(
-                            "INSERT INTO LIBRARY ({:?}) VALUES (\"{}\")",
-                            &server.user_id, &x
-                          )
-                          .as_str(),
-                        )
-                        .execute(&database)
-                        .await
-                        .expect("insert error");
-                      }
-                    }
-                  }
-                } else {
-                  let series_id = itemlist[0].SeriesId.clone().unwrap();
-                  let mut item: Item = itemlist[0].clone();
-                  for x in &serialized_server.Items {
-                    if x.Id == series_id {
-                      item = x.clone();
-                      break;
-                    }
-                  }
-                  if item.Type != Type::Series {
-                    eprintln!(
-                      "Failed to find a Series object that belongs to \"{}\"",
-                      item.Id
-                    );
-                    continue;
-                  }
-
-                  itemlist.sort_by_key(|i| i.IndexNumber.unwrap());
-                  let mut desc = String::new();
-                  let mut a_languages: Vec<String> = vec![];
-                  let mut s_languages: Vec<String> = vec![];
-                  let mut v_resolutions: Vec<String> = vec![];
-                  let mut ratings: Vec<f64> = vec![];
-                  let mut total_runtime: u64 = 0;
-
-                  let mut current_start: i32 = -1;
-                  for (i, episode) in itemlist.iter().enumerate() {
-                    if episode.MediaStreams.is_some() {
-                      for x in episode.MediaStreams.clone().unwrap() {
-                        if x.Type == "Video" {
-                          let resolution: String;
-                          let scan_type: char;
-
-                          if x.IsInterlaced {
-                            scan_type = 'i';
-                          } else {
-                            scan_type = 'p';
-                          }
-
-                          if let Some(height) = x.Height {
-                            resolution = height.to_string() + &scan_type.to_string();
-                          } else {
-                            resolution = String::from("?") + &scan_type.to_string();
-                          }
-
-                          if !v_resolutions.contains(&resolution) {
-                            v_resolutions.push(resolution);
-                          }
-                        } else if x.Type == "Audio" {
-                          let lang = x.Language.unwrap_or("?".to_string());
-                          if !a_languages.contains(&lang) {
-                            a_languages.push(lang);
-                          }
-                        } else if x.Type == "Subtitle" {
-                          let lang = x.Language.unwrap_or("?".to_string());
-                          if !s_languages.contains(&lang) {
-                            s_languages.push(lang);
-                          }
-                        }
-                      }
-                    }
-
-                    if let Some(rating) = episode.CommunityRating {
-                      ratings.push(rating);
-                    }
-
-                    if let Some(runtime) = episode.RunTimeTicks {
-                      total_runtime += runtime;
-                    }
-
-                    let index_start = episode.IndexNumber.unwrap() as i32;
-                    let index_end = if let Some(end) = episode.IndexNumberEnd {
-                      end as i32
-                    } else {
-                      index_start
-                    };
-                    let item_name_full = match episode.IndexNumberEnd {
-                      Some(indexend) => {
-                        format!(
-                          "S{:02}E{:02}-{:02}",
-                          episode.ParentIndexNumber.unwrap_or(0),
-                          episode.IndexNumber.unwrap_or(0),
-                          indexend
-                        )
-                      },
-                      None => {
-                        format!(
-                          "S{:02}E{:02}",
-                          episode.ParentIndexNumber.unwrap_or(0),
-                          episode.IndexNumber.unwrap_or(0)
-                        )
-                      },
-                    };
-                    let item_name_end = match episode.IndexNumberEnd {
-                      Some(indexend) => {
-                        format!(
-                          "S{:02}E{:02}",
-                          episode.ParentIndexNumber.unwrap_or(0),
-                          indexend
-                        )
-                      },
-                      None => {
-                        format!(
-                          "S{:02}E{:02}",
-                          episode.ParentIndexNumber.unwrap_or(0),
-                          episode.IndexNumber.unwrap_or(0)
-                        )
-                      },
-                    };
-                    let item_name_start = format!(
-                      "S{:02}E{:02}",
-                      episode.ParentIndexNumber.unwrap_or(0),
-                      episode.IndexNumber.unwrap_or(0)
-                    );
-
-                    if itemlist.len() - 1 == i {
-                      if current_start == -1 {
-                        desc.push_str(&format!("{}", item_name_full));
-                      } else {
-                        desc.push_str(&format!("-{}", item_name_end));
-                      }
-                    } else if i == 0 || current_start == -1 {
-                      if itemlist[i + 1].IndexNumber.unwrap() as i32 != index_end + 1 {
-                        desc.push_str(&format!("{}, ", item_name_full));
-                        current_start = -1;
-                        continue;
-                      } else {
-                        desc.push_str(&item_name_start);
-                      }
-                    } else if itemlist[i + 1].IndexNumber.unwrap() as i32 != index_end + 1 {
-                      desc.push_str(&format!("-{}, ", item_name_end));
-                      current_start = -1;
-                      continue;
-                    }
-                    current_start = index_start;
-                  }
+              // A re-scan of the server can assign new ids to the whole library. Instead of
+              // announcing all of it, admins are asked once and nothing happens until they decide.
+              let held_back = max_announcements > 0
+                && raw_new_items.len() > max_announcements
+                && (!paused || server.pause_queue);
+              if held_back && server.valve_state != 2 {
+                if server.valve_state == 0 {
+                  commands::resync::alert(&ctx, &server, raw_new_items.len()).await;
+                }
+                continue;
+              }
+              if server.valve_state != 0 {
+                set_valve_state(server.id, 0).await;
+              }
 
-                  let image = format!(
-                    "{}/Items/{}/Images/Primary?Quality=100",
-                    server.domain,
-                    item.clone().SeasonId.unwrap_or(item.clone().Id)
+              let filters = get_filters(server.id).await;
+              for itemlist in new_items.iter_mut() {
+                let Some(announcement) = announcement::render(
+                  &server,
+                  itemlist,
+                  &serialized_server.Items,
+                  &pre_season_items,
+                  &pre_episode_items,
+                ) else {
+                  continue;
+                };
+
+                // Filtered items are still marked as seen, so they won't be evaluated again.
+                if let Some(filter) = announcement.filtered_by(&filters, &serialized_server.Items) {
+                  println!(
+                    "Skipping \"{}\" due to filter: {} {}",
+                    announcement.title, filter.kind, filter.value
                   );
+                  mark_seen(&server.user_id, &announcement.ids).await;
+                  continue;
+                }
 
-                  let time = (total_runtime as f64) / 10000000.0;
-                  let formatted_runtime: String = if time > 60.0 {
-                    if (time / 60.0) > 60.0 {
-                      format!(
-                        "{:02}:{:02}:{:02}",
-                        ((time / 60.0) / 60.0).trunc(),
-                        ((((time / 60.0) / 60.0) - ((time / 60.0) / 60.).trunc()) * 60.0).trunc(),
-                        (((time / 60.0) - (time / 60.0).trunc()) * 60.0).trunc()
-                      )
-                    } else {
-                      format!(
-                        "00:{:02}:{:02}",
-                        (time / 60.0).trunc(),
-                        (((time / 60.0) - (time / 60.0).trunc()) * 60.0).trunc()
-                      )
-                    }
-                  } else {
-                    format!("00:00:{time:02}")
-                  };
-
-                  let mut fields = Vec::new();
-                  fields.push((
-                    ":star: — Rating".to_string(),
-                    format!("{:.2}", ratings.iter().sum::<f64>() / ratings.len() as f64),
-                    true,
-                  ));
-                  fields.push((
-                    ":film_frames: — Runtime".to_string(),
-                    formatted_runtime.to_string(),
-                    true,
-                  ));
-                  fields.push((
-                    ":frame_photo: — Resolution".to_string(),
-                    v_resolutions.join(", "),
-                    true,
-                  ));
-                  fields.push((
-                    ":loud_sound: — Languages".to_string(),
-                    // 205 is the exact max amount of langs to show (they should all be 3 chars long)
-                    a_languages
-                      .iter()
-                      .take(205)
-                      .map(|s| s.as_str())
-                      .collect::<Vec<&str>>()
-                      .join(", "),
-                    false,
-                  ));
-
-                  if !s_languages.is_empty() {
-                    fields.push((
-                      ":notepad_spiral: — Languages".to_string(),
-                      s_languages
-                        .iter()
-                        .take(205)
-                        .map(|s| s.as_str())
-                        .collect::<Vec<&str>>()
-                        .join(", "),
-                      false,
-                    ));
+                if paused {
+                  if server.pause_queue {
+                    queue_announcement(server.id, &announcement).await;
                   }
+                  mark_seen(&server.user_id, &announcement.ids).await;
+                  continue;
+                }
 
-                  let mut embed = CreateEmbed::default();
-                  for (name, value, inline) in &fields {
-                    embed = embed.field(name.clone(), value.clone(), *inline);
-                  }
+                if server.digest_schedule.is_some() || quiet {
+                  queue_announcement(server.id, &announcement).await;
+                  mark_seen(&server.user_id, &announcement.ids).await;
+                  continue;
+                }
 
-                  let res = ChannelId::new(server.channel_id as u64)
-                    .send_message(
-                      &ctx,
-                      CreateMessage::new()
-                        .add_embed(
-                          CreateEmbed::new()
-                            .title(item.to_string())
-                            .description(desc)
-                            .image(image),
-                        )
-                        .add_embed(embed),
+                match announcement::deliver(&ctx, &server, &announcement).await {
+                  Ok(_) => {
+                    mark_seen(&server.user_id, &announcement.ids).await;
+                    follows::notify(&ctx, &server, &announcement).await;
+                  },
+                  Err(why) => {
+                    eprintln!("Error sending message: {why:?}");
+                    set_last_error(
+                      server.id,
+                      chrono::offset::Utc::now().timestamp(),
+                      &format!("Failed to send an announcement: {why}"),
                     )
                     .await;
-
-                  if let Err(why) = res {
-                    eprintln!("Error sending message: {why:?}");
-                  } else {
-                    for x in itemlist {
-                      let database = sqlx::sqlite::SqlitePoolOptions::new()
-                        .max_connections(5)
-                        .connect_with(
-                          sqlx::sqlite::SqliteConnectOptions::new()
-                            .filename("jellycord.sqlite")
-                            .create_if_missing(true),
-                        )
-                        .await
-                        .expect("Couldn't connect to database");
-                      sqlx::query(
-                        format!(
-                          "INSERT INTO LIBRARY ({:?}) VALUES (\"{}\")",
-                          &server.user_id, &x.Id
-                        )
-                        .as_str(),
-                      )
-                      .execute(&database)
-                      .await
-                      .expect("insert error");
-                    }
-                  }
+                  },
                 }
               }
             } else {
               eprintln!("Failed to connect to the server. {}", server.domain);
+              set_last_error(
+                server.id,
+                chrono::offset::Utc::now().timestamp(),
+                "Failed to connect to the server.",
+              )
+              .await;
               tokio::time::sleep(Duration::from_secs(5)).await; // Don't ddos the dns server.
               continue;
             }
@@ -1024,6 +708,11 @@ async fn main() {
   if env::var("SETUP") == Ok("1".to_string()) {
     exit(0x100);
   };
+  let command = env::args().nth(1);
+  if command.as_deref() == Some("generate-key") {
+    println!("{}", crypto::generate_key());
+    exit(0);
+  }
   let settings_file_raw = Config::builder()
     .add_source(File::from(Path::new(&"./jellycord.yaml".to_string())))
     .build()
@@ -1031,11 +720,54 @@ async fn main() {
   let serialized = settings_file_raw
     .try_deserialize::<ConfigFile>()
     .expect("Reading config file.");
+
+  let encryption_key = env::var("JELLYCORD_ENCRYPTION_KEY")
+    .ok()
+    .or(serialized.encryption_key.clone())
+    .filter(|key| !key.trim().is_empty())
+    .map(|key| crypto::parse_key(&key).expect("Reading encryption key."));
+  match command.as_deref() {
+    Some("rotate-key") => {
+      // The new key is read from the environment, so it doesn't end up in the shell history.
+      let Ok(new_key) = env::var("JELLYCORD_NEW_ENCRYPTION_KEY") else {
+        eprintln!(
+          "Set JELLYCORD_NEW_ENCRYPTION_KEY to the new key (see `jellycord generate-key`)."
+        );
+        exit(1);
+      };
+      let new_key = crypto::parse_key(&new_key).expect("Reading new encryption key.");
+      match crypto::rotate(encryption_key.as_ref(), &new_key).await {
+        Ok(count) => {
+          println!(
+            "Re-encrypted {count} API key(s) and webhook URL(s). Replace the configured encryption key now."
+          );
+          exit(0);
+        },
+        Err(why) => {
+          eprintln!("{why}");
+          exit(1);
+        },
+      }
+    },
+    Some(command) => {
+      eprintln!("Unknown command \"{command}\". Available: generate-key, rotate-key");
+      exit(1);
+    },
+    None => {},
+  }
+  crypto::init(encryption_key);
+  if let Err(why) = crypto::encrypt_stored_tokens().await {
+    eprintln!("{why}");
+    exit(1);
+  }
+
   loop {
     let intents = GatewayIntents::non_privileged() | GatewayIntents::MESSAGE_CONTENT;
     let client = Client::builder(serialized.discord_token.clone(), intents)
       .event_handler(Handler {
         is_loop_running: AtomicBool::new(false),
+        max_announcements_per_cycle: serialized.max_announcements_per_cycle,
+        dev_guild: serialized.dev_guild.map(GuildId::new),
       })
       .await;
     if client.is_err() {
@@ -1049,7 +781,213 @@ async fn main() {
   }
 }
 
-async fn get_serialized_page(url: String) -> Result<MediaResponse, ()> {
+/// Returns the libraries (user views) that can contain announceable items, including the ones
+/// hidden from "My Media".
+async fn get_views(server: &Instance) -> Result<Vec<View>, ()> {
+  let views: ViewList = get_serialized_page(format!(
+    "{}/Users/{}/Views?includeHidden=true&api_key={}",
+    server.domain, server.user_id, server.token
+  ))
+  .await?;
+  Ok(
+    views
+      .Items
+      .into_iter()
+      .filter(|view| {
+        !matches!(
+          view.CollectionType.as_deref(),
+          Some("boxsets" | "playlists" | "livetv" | "music")
+        )
+      })
+      .collect(),
+  )
+}
+
+/// Returns the libraries of an instance that aren't excluded through `/libraries`.
+async fn get_included_views(server: &Instance) -> Result<Vec<View>, ()> {
+  let libraries = get_libraries(server.id).await;
+  Ok(
+    get_views(server)
+      .await?
+      .into_iter()
+      .filter(|view| server.includes_library(&libraries, &view.Id))
+      .collect(),
+  )
+}
+
+impl Instance {
+  /// Whether items of a library should be announced.
+  fn includes_library(&self, libraries: &[String], view_id: &String) -> bool {
+    match self.library_mode {
+      1 => libraries.contains(view_id),
+      2 => !libraries.contains(view_id),
+      _ => true,
+    }
+  }
+}
+
+/// Fetches every item of a single library, or of the whole server.
+async fn get_library_items(server: &Instance, view_id: Option<&str>) -> Result<Vec<Item>, ()> {
+  let parent = view_id
+    .map(|view_id| format!("&ParentId={view_id}"))
+    .unwrap_or_default();
+  let response: MediaResponse = get_serialized_page(format!(
+    "{}/Users/{}/Items?api_key={}{}&Recursive=true&IncludeItemTypes=Movie,Series,Episode,Season,Special&Fields=MediaStreams,Genres,Tags&collapseBoxSetItems=False",
+    server.domain, server.user_id, server.token, parent
+  ))
+  .await?;
+  Ok(response.Items)
+}
+
+/// Fetches every item of the instance.
+///
+/// Restricted instances are fetched library by library, skipping the libraries excluded through
+/// `/libraries`. Others are fetched in a single query, and only look up the library of each item
+/// if a route or mention depends on it.
+async fn get_serialized_library(server: &Instance) -> Result<MediaResponse, ()> {
+  if server.library_mode == 0 {
+    let mut items = get_library_items(server, None).await?;
+    let by_library = get_routes(server.id)
+      .await
+      .iter()
+      .any(|route| route.kind == "library")
+      || get_mentions(server.id)
+        .await
+        .iter()
+        .any(|mention| mention.kind == "library");
+    if by_library {
+      for view in get_views(server).await? {
+        let ids: HashSet<String> = get_library_ids(server, &view.Id).await?;
+        for item in items.iter_mut().filter(|item| item.LibraryId.is_none()) {
+          if ids.contains(&item.Id) {
+            item.LibraryId = Some(view.Id.clone());
+          }
+        }
+      }
+    }
+    return Ok(MediaResponse { Items: items });
+  }
+
+  let mut items: Vec<Item> = vec![];
+  let mut ids: HashSet<String> = HashSet::new();
+  for view in get_included_views(server).await? {
+    for mut item in get_library_items(server, Some(&view.Id)).await? {
+      // Movies can show up in several libraries, the first one wins.
+      if !ids.insert(item.Id.clone()) {
+        continue;
+      }
+      item.LibraryId = Some(view.Id.clone());
+      items.push(item);
+    }
+  }
+  Ok(MediaResponse { Items: items })
+}
+
+/// Returns the ids of the items of a single library, without any of their metadata.
+async fn get_library_ids(server: &Instance, view_id: &str) -> Result<HashSet<String>, ()> {
+  let response: MediaResponse = get_serialized_page(format!(
+    "{}/Users/{}/Items?api_key={}&ParentId={}&Recursive=true&IncludeItemTypes=Movie,Series,Episode,Season,Special&EnableImages=false&EnableUserData=false&collapseBoxSetItems=False",
+    server.domain, server.user_id, server.token, view_id
+  ))
+  .await?;
+  Ok(response.Items.into_iter().map(|item| item.Id).collect())
+}
+
+/// Searches the items of an instance by name. Restricted instances only search their libraries.
+async fn search_items(server: &Instance, term: &str, types: &str) -> Result<Vec<Item>, ()> {
+  let mut items: Vec<Item> = vec![];
+  for parent in get_parents(server).await? {
+    let mut params = vec![
+      ("api_key", server.token.as_str()),
+      ("searchTerm", term),
+      ("IncludeItemTypes", types),
+      ("Recursive", "true"),
+      ("Fields", "MediaStreams,Genres,Tags"),
+      ("Limit", "25"),
+    ];
+    params.extend(parent.as_deref().map(|parent| ("ParentId", parent)));
+    let url = reqwest::Url::parse_with_params(
+      &format!("{}/Users/{}/Items", server.domain, server.user_id),
+      &params,
+    )
+    .map_err(|_| ())?;
+    let response: MediaResponse = get_serialized_page(url.to_string()).await?;
+    for item in response.Items {
+      if !items.iter().any(|found| found.Id == item.Id) {
+        items.push(item);
+      }
+    }
+  }
+  items.truncate(25);
+  Ok(items)
+}
+
+/// Returns the most recently added items of an instance, newest first. Restricted instances only
+/// return items of their libraries.
+async fn get_latest_items(server: &Instance, types: &str, limit: i64) -> Result<Vec<Item>, ()> {
+  let mut items: Vec<Item> = vec![];
+  for parent in get_parents(server).await? {
+    let limit = limit.to_string();
+    let mut params = vec![
+      ("api_key", server.token.as_str()),
+      ("IncludeItemTypes", types),
+      ("Limit", limit.as_str()),
+      ("Fields", "MediaStreams,Genres,Tags,DateCreated"),
+      ("GroupItems", "false"),
+    ];
+    params.extend(parent.as_deref().map(|parent| ("ParentId", parent)));
+    let url = reqwest::Url::parse_with_params(
+      &format!("{}/Users/{}/Items/Latest", server.domain, server.user_id),
+      &params,
+    )
+    .map_err(|_| ())?;
+    let latest: Vec<Item> = get_serialized_page(url.to_string()).await?;
+    for item in latest {
+      if !items.iter().any(|found| found.Id == item.Id) {
+        items.push(item);
+      }
+    }
+  }
+  // Dates share jellyfin's ISO 8601 format, so they sort as strings.
+  items.sort_by(|a, b| b.DateCreated.cmp(&a.DateCreated));
+  items.truncate(limit as usize);
+  Ok(items)
+}
+
+/// Returns the libraries to query one by one for browsing, or a single query over every library
+/// if the instance isn't restricted.
+async fn get_parents(server: &Instance) -> Result<Vec<Option<String>>, ()> {
+  if server.library_mode == 0 {
+    return Ok(vec![None]);
+  }
+  Ok(
+    get_included_views(server)
+      .await?
+      .into_iter()
+      .map(|view| Some(view.Id))
+      .collect(),
+  )
+}
+
+/// Returns the jellyfin user of an instance.
+async fn get_user(server: &Instance) -> Result<UserList, ()> {
+  get_serialized_page(format!(
+    "{}/Users/{}?api_key={}",
+    server.domain, server.user_id, server.token
+  ))
+  .await
+}
+
+/// Returns a single item, if the user of the instance can access it.
+async fn get_item(server: &Instance, item_id: &str) -> Result<Item, ()> {
+  get_serialized_page(format!(
+    "{}/Users/{}/Items/{}?api_key={}",
+    server.domain, server.user_id, item_id, server.token
+  ))
+  .await
+}
+
+async fn get_serialized_page<T: DeserializeOwned>(url: String) -> Result<T, ()> {
   let client = reqwest::Client::new();
   let web_request = client
     .get(url)
@@ -1072,7 +1010,7 @@ async fn get_serialized_page(url: String) -> Result<MediaResponse, ()> {
     },
   };
 
-  match serde_json::from_str::<MediaResponse>(&webpage_as_string) {
+  match serde_json::from_str::<T>(&webpage_as_string) {
     Ok(serialized) => Ok(serialized),
     Err(e) => {
       eprintln!("Error: {}", e);